@@ -1,10 +1,14 @@
+use crate::modulus::Modulus;
 use crate::ntt::Ntt;
-use crate::utils::{generate_primes, invmod};
+use crate::prime::{is_prime, ntt_primes};
 
 use num_bigint::BigInt;
-use num_traits::{One, Zero};
+use num_traits::One;
 
-use rayon::prelude::*;
+#[cfg(feature = "parallel")]
+use std::sync::Arc;
+
+use crate::parallel::*;
 
 ///
 /// Chinese remainder theorem
@@ -13,15 +17,47 @@ use rayon::prelude::*;
 #[derive(Debug)]
 pub struct Crt {
     primes: Vec<u64>,
+    moduli: Vec<Modulus>,
     pub modulus: BigInt,
     pub ntts: Vec<Ntt>,
-    crt_vals: Vec<BigInt>,
-    crt_vals_inv: Vec<BigInt>,
+    // (m_0 * ... * m_{i-1})^-1 mod m_i, for Garner's algorithm
+    garner_inv: Vec<u64>,
+    // m_j mod m_i for j < i
+    garner_primes: Vec<Vec<u64>>,
+    #[cfg(feature = "parallel")]
+    pool: Option<Arc<ThreadPool>>,
 }
 
 impl Crt {
+    ///
+    /// A Crt of `num_primes` distinct NTT primes for polynomials of degree N, each as close as
+    /// possible to 2^prime_size, see `prime::ntt_primes`.
+    ///
+    /// The primes only depend on the arguments, so two bases that need to be coprime should
+    /// take their primes from a single `ntt_primes` call and use `from_primes`.
+    ///
     pub fn new(num_primes: u64, prime_size: u64, poly_degree: u64) -> Self {
-        let primes = generate_primes(num_primes, prime_size, 2 * poly_degree);
+        let primes = ntt_primes(&vec![prime_size as u32; num_primes as usize], poly_degree);
+        Crt::from_primes(&primes, poly_degree)
+    }
+
+    ///
+    /// Build a Crt from an explicit list of distinct primes, which all need to be 1 mod 2N for
+    /// the NTT of polynomials of degree N. See `prime::ntt_primes` to select them.
+    ///
+    pub fn from_primes(primes: &[u64], poly_degree: u64) -> Self {
+        assert!(!primes.is_empty(), "Crt needs at least one prime");
+        for (i, p) in primes.iter().enumerate() {
+            assert!(is_prime(*p), "Crt: {} is not prime", p);
+            assert!(
+                !primes[..i].contains(p),
+                "Crt: prime {} appears more than once",
+                p
+            );
+        }
+        let primes = primes.to_vec();
+
+        let moduli: Vec<Modulus> = primes.iter().map(|&p| Modulus::new(p)).collect();
 
         let ntts = primes
             .par_iter()
@@ -33,20 +69,60 @@ impl Crt {
             modulus = modulus * p;
         }
 
-        let crt_vals: Vec<BigInt> = primes.par_iter().map(|p| &modulus / p).collect();
+        let garner_primes: Vec<Vec<u64>> = moduli
+            .iter()
+            .enumerate()
+            .map(|(i, m)| primes[..i].iter().map(|&p| m.reduce(p)).collect())
+            .collect();
 
-        let crt_vals_inv: Vec<BigInt> = (0..num_primes as usize)
-            .into_par_iter()
-            .map(|i| invmod(&crt_vals[i], primes[i]))
+        let garner_inv = moduli
+            .iter()
+            .zip(&garner_primes)
+            .map(|(m, prev)| {
+                let prod = prev.iter().fold(1, |acc, &p| m.mul_mod(acc, p));
+                m.inv_mod(prod).expect("Crt: moduli need to be coprime")
+            })
             .collect();
 
         Crt {
             primes,
+            moduli,
             modulus,
             ntts,
-            crt_vals,
-            crt_vals_inv,
+            garner_inv,
+            garner_primes,
+            #[cfg(feature = "parallel")]
+            pool: None,
+        }
+    }
+
+    ///
+    /// Run the parallel work on polynomials using this Crt (transforms, reconstruction, ...) on
+    /// the given thread pool rather than on rayon's global pool
+    ///
+    #[cfg(feature = "parallel")]
+    pub fn with_thread_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    ///
+    /// Run `op` on the thread pool of this Crt, or on the current one if there is none
+    ///
+    pub fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        #[cfg(feature = "parallel")]
+        if let Some(pool) = &self.pool {
+            return pool.install(op);
         }
+        op()
+    }
+
+    pub fn primes(&self) -> &[u64] {
+        &self.primes
+    }
+
+    pub fn moduli(&self) -> &[Modulus] {
+        &self.moduli
     }
 
     ///
@@ -59,20 +135,302 @@ impl Crt {
             .collect()
     }
 
+    ///
+    /// Take value X of any size and return a_i mod m_i
+    ///
+    pub fn decompose(&self, value: &BigInt) -> Vec<u64> {
+        self.moduli.iter().map(|m| m.reduce_bigint(value)).collect()
+    }
+
     ///
     /// Take an array of a_i (mod m_i) to get value X (mod m_0 * m_1 ...)
     ///
     pub fn reconstruct(&self, values: Vec<i128>) -> BigInt {
         assert_eq!(values.len(), self.primes.len());
 
-        let mut reg = BigInt::zero();
+        let residues: Vec<u64> = values
+            .iter()
+            .zip(&self.primes)
+            .map(|(v, &p)| v.rem_euclid(p as i128) as u64)
+            .collect();
+        self.garner(|i| residues[i])
+    }
+
+    ///
+    /// Decompose every coefficient of a polynomial, returning one vector of residues per modulus
+    ///
+    pub fn decompose_poly(&self, coef: &[BigInt]) -> Vec<Vec<u64>> {
+        // All the residues of one coefficient per task, as there are far more coefficients than
+        // moduli
+        let per_coef: Vec<Vec<u64>> =
+            self.install(|| coef.par_iter().map(|c| self.decompose(c)).collect());
+
+        let mut residues = vec![Vec::with_capacity(coef.len()); self.moduli.len()];
+        for values in per_coef {
+            for (r, x) in residues.iter_mut().zip(values) {
+                r.push(x);
+            }
+        }
+        residues
+    }
+
+    ///
+    /// Reconstruct every coefficient of a polynomial in [0, m_0 * m_1 ...) from one vector of
+    /// residues per modulus
+    ///
+    pub fn reconstruct_poly(&self, residues: &[Vec<u64>]) -> Vec<BigInt> {
+        assert_eq!(residues.len(), self.primes.len());
+        let len = residues.first().map_or(0, |r| r.len());
+        assert!(
+            residues.iter().all(|r| r.len() == len),
+            "reconstruct_poly: residues have different lengths"
+        );
+
+        self.install(|| {
+            (0..len)
+                .into_par_iter()
+                .map(|j| self.garner(|i| residues[i][j]))
+                .collect()
+        })
+    }
+
+    ///
+    /// Garner's algorithm: find the mixed-radix digits y_i of X, such that
+    /// X = y_0 + y_1 * m_0 + y_2 * m_0 * m_1 + ..., using only word-sized arithmetic, and then
+    /// evaluate that sum with a BigInt.
+    ///
+    fn garner(&self, residue: impl Fn(usize) -> u64) -> BigInt {
+        let num_primes = self.primes.len();
+        let mut digits = vec![0u64; num_primes];
+
+        for i in 0..num_primes {
+            let m = &self.moduli[i];
+
+            // y_0 + y_1 * m_0 + ... + y_{i-1} * m_0 * ... * m_{i-2} (mod m_i), with Horner's rule
+            let prev = (0..i).rev().fold(0, |acc, j| {
+                m.add_mod(
+                    m.mul_mod(acc, self.garner_primes[i][j]),
+                    m.reduce(digits[j]),
+                )
+            });
+
+            digits[i] = m.mul_mod(m.sub_mod(m.reduce(residue(i)), prev), self.garner_inv[i]);
+        }
+
+        let mut value = BigInt::from(0u64);
+        for i in (0..num_primes).rev() {
+            value = value * self.primes[i] + digits[i];
+        }
+        value
+    }
+}
+
+///
+/// Converts residues of a value modulo the primes of one Crt basis Q = q_0 * q_1 * ... into
+/// residues modulo the primes of another basis P = p_0 * p_1 * ..., without going through BigInt.
+///
+/// Both conversions start from y_i = [x_i * (Q/q_i)^-1]_{q_i}, so that
+/// sum_i y_i * (Q/q_i) = x + v * Q for some 0 <= v < k, the number of primes in Q.
+///
+#[derive(Debug)]
+pub struct BaseConverter {
+    from: Vec<Modulus>,
+    to: Vec<Modulus>,
+    // (Q/q_i)^-1 mod q_i, with its Shoup precomputation
+    q_hat_inv: Vec<(u64, u64)>,
+    // Q/q_i mod p_j, indexed [j][i]
+    q_hat_mod_p: Vec<Vec<u64>>,
+    // Q mod p_j
+    q_mod_p: Vec<u64>,
+}
+
+impl BaseConverter {
+    pub fn new(from: &Crt, to: &Crt) -> Self {
+        let (from, to) = (from.moduli(), to.moduli());
+
+        let q_hat_inv = from
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let q_hat = from
+                    .iter()
+                    .enumerate()
+                    .filter(|&(l, _)| l != i)
+                    .fold(1, |acc, (_, q)| m.mul_mod(acc, q.value()));
+                let inv = m
+                    .inv_mod(q_hat)
+                    .expect("BaseConverter: moduli need to be coprime");
+                (inv, m.shoup(inv))
+            })
+            .collect();
+
+        let q_hat_mod_p = to
+            .iter()
+            .map(|p| {
+                (0..from.len())
+                    .map(|i| {
+                        from.iter()
+                            .enumerate()
+                            .filter(|&(l, _)| l != i)
+                            .fold(1, |acc, (_, q)| p.mul_mod(acc, q.value()))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let q_mod_p = to
+            .iter()
+            .map(|p| from.iter().fold(1, |acc, q| p.mul_mod(acc, q.value())))
+            .collect();
+
+        BaseConverter {
+            from: from.to_vec(),
+            to: to.to_vec(),
+            q_hat_inv,
+            q_hat_mod_p,
+            q_mod_p,
+        }
+    }
+
+    ///
+    /// y_i = [x_i * (Q/q_i)^-1]_{q_i} for every coefficient
+    ///
+    fn scaled_residues(&self, residues: &[Vec<u64>]) -> Vec<Vec<u64>> {
+        assert_eq!(residues.len(), self.from.len());
+        residues
+            .par_iter()
+            .zip(&self.from)
+            .zip(&self.q_hat_inv)
+            .map(|((x, m), &(inv, inv_shoup))| {
+                x.iter().map(|&c| m.mul_shoup(c, inv, inv_shoup)).collect()
+            })
+            .collect()
+    }
+
+    ///
+    /// Fast (approximate) base conversion: for x in [0, Q), returns x + v * Q modulo every p_j,
+    /// for some 0 <= v < k that depends on the coefficient.
+    ///
+    pub fn fast_convert(&self, residues: &[Vec<u64>]) -> Vec<Vec<u64>> {
+        let y = self.scaled_residues(residues);
+        let len = y.first().map_or(0, |r| r.len());
+        self.fast_convert_with(&y, &vec![0; len])
+    }
+
+    ///
+    /// Exact base conversion in the style of Halevi, Polyakov and Shoup: the overflow v is
+    /// computed as round(sum_i y_i / q_i) in floating point and subtracted, so the result is the
+    /// centered representative of x in [-Q/2, Q/2) modulo every p_j.
+    ///
+    /// The rounding is only ambiguous for x within about k * 2^-53 * Q of +-Q/2, which is far
+    /// outside the range of values that appear in practice.
+    ///
+    pub fn exact_convert(&self, residues: &[Vec<u64>]) -> Vec<Vec<u64>> {
+        let y = self.scaled_residues(residues);
+        let len = y.first().map_or(0, |r| r.len());
+
+        let overflow: Vec<u64> = (0..len)
+            .map(|c| {
+                let frac: f64 = y
+                    .iter()
+                    .zip(&self.from)
+                    .map(|(y, q)| y[c] as f64 / q.value() as f64)
+                    .sum();
+                frac.round() as u64
+            })
+            .collect();
+
+        self.fast_convert_with(&y, &overflow)
+    }
+
+    ///
+    /// sum_i y_i * (Q/q_i) - v * Q modulo every p_j, for a given overflow v per coefficient
+    ///
+    fn fast_convert_with(&self, y: &[Vec<u64>], overflow: &[u64]) -> Vec<Vec<u64>> {
+        self.to
+            .par_iter()
+            .zip(&self.q_hat_mod_p)
+            .zip(&self.q_mod_p)
+            .map(|((p, q_hat), &q_mod_p)| {
+                overflow
+                    .iter()
+                    .enumerate()
+                    .map(|(c, &v)| {
+                        let sum = y.iter().zip(q_hat).fold(0, |acc, (y, &q_hat)| {
+                            p.add_mod(acc, p.mul_mod(p.reduce(y[c]), q_hat))
+                        });
+                        p.sub_mod(sum, p.mul_mod(p.reduce(v), q_mod_p))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+///
+/// The extended basis Q u P used by hybrid key switching, where the key switching keys live
+/// modulo Q * P while ciphertexts live modulo Q.
+///
+/// Residues over Q u P are stored with the primes of Q first, followed by the primes of P.
+///
+#[derive(Debug)]
+pub struct ExtendedBasis {
+    q_to_p: BaseConverter,
+    p_to_q: BaseConverter,
+    // P^-1 mod q_i
+    p_inv_mod_q: Vec<u64>,
+}
+
+impl ExtendedBasis {
+    pub fn new(q: &Crt, p: &Crt) -> Self {
+        let p_inv_mod_q = q
+            .moduli()
+            .iter()
+            .map(|m| {
+                let p_mod_q = p.primes().iter().fold(1, |acc, &p| m.mul_mod(acc, p));
+                m.inv_mod(p_mod_q)
+                    .expect("ExtendedBasis: Q and P need to be coprime")
+            })
+            .collect();
 
-        for i in 0..values.len() {
-            let i_val = (values[i] * &self.crt_vals_inv[i]) % self.primes[i];
-            let i_val = (i_val * &self.crt_vals[i]) % &self.modulus;
-            reg += i_val;
-            reg %= &self.modulus;
+        ExtendedBasis {
+            q_to_p: BaseConverter::new(q, p),
+            p_to_q: BaseConverter::new(p, q),
+            p_inv_mod_q,
         }
-        reg
+    }
+
+    ///
+    /// ModUp: extend x, given modulo Q, to the same (centered) value modulo Q * P
+    ///
+    pub fn mod_up(&self, residues_q: &[Vec<u64>]) -> Vec<Vec<u64>> {
+        let mut extended = residues_q.to_vec();
+        extended.extend(self.q_to_p.exact_convert(residues_q));
+        extended
+    }
+
+    ///
+    /// ModDown: take x modulo Q * P and return round(x / P) modulo Q
+    ///
+    pub fn mod_down(&self, residues_qp: &[Vec<u64>]) -> Vec<Vec<u64>> {
+        let num_q = self.q_to_p.from.len();
+        assert_eq!(residues_qp.len(), num_q + self.q_to_p.to.len());
+
+        let (x_q, x_p) = residues_qp.split_at(num_q);
+
+        // [x]_P centered, so x - [x]_P is the multiple of P closest to x
+        let x_p_in_q = self.p_to_q.exact_convert(x_p);
+
+        x_q.par_iter()
+            .zip(&x_p_in_q)
+            .zip(&self.q_to_p.from)
+            .zip(&self.p_inv_mod_q)
+            .map(|(((x, r), m), &p_inv)| {
+                x.iter()
+                    .zip(r)
+                    .map(|(&x, &r)| m.mul_mod(m.sub_mod(x, r), p_inv))
+                    .collect()
+            })
+            .collect()
     }
 }
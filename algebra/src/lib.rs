@@ -1,5 +1,16 @@
 #![feature(int_log)]
 
 pub mod crt;
+pub mod modulus;
 pub mod ntt;
+///
+/// Parallel iterators and `join`, backed by rayon when the `parallel` feature is enabled (the
+/// default) and by plain sequential iterators otherwise, so the rest of the code can be written
+/// once with `par_iter()`, `par_iter_mut()` and `into_par_iter()`.
+///
+/// With rayon, work runs on the current thread pool. Use `Crt::with_thread_pool` to run the work
+/// on polynomials using a Crt on a specific pool instead.
+///
+pub mod parallel;
+pub mod prime;
 pub mod utils;
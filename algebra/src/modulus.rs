@@ -0,0 +1,356 @@
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive};
+
+///
+/// A modulus p < 2^62 with precomputed constants for fast modular arithmetic on u64
+///
+/// Products are reduced with Barrett reduction, multiplications by a fixed operand can use
+/// Shoup's precomputation, and Montgomery multiplication is available for long chains of
+/// products (e.g. exponentiation). Values are represented in [0, p) unless noted otherwise.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Modulus {
+    value: u64,
+    bits: u32,
+    // floor(4^bits / p), for Barrett reduction
+    barrett_ratio: u64,
+    // -p^-1 mod 2^64, for Montgomery reduction
+    montgomery_inv: u64,
+    // 2^128 mod p, to move values into Montgomery form
+    montgomery_r2: u64,
+}
+
+impl Modulus {
+    pub fn new(value: u64) -> Self {
+        assert!(value > 1, "Modulus needs to be larger than 1");
+        assert!(value < 1 << 62, "Modulus needs to be below 2^62");
+
+        let bits = 64 - value.leading_zeros();
+        let barrett_ratio = ((1u128 << (2 * bits)) / value as u128) as u64;
+
+        // Newton's iteration doubles the number of correct low bits of p^-1 every step. Only
+        // defined for odd moduli, which is all Montgomery reduction works with anyway.
+        let mut inv = 1u64;
+        for _ in 0..6 {
+            inv = inv.wrapping_mul(2u64.wrapping_sub(value.wrapping_mul(inv)));
+        }
+        let montgomery_r2 = ((u128::MAX % value as u128 + 1) % value as u128) as u64;
+
+        Modulus {
+            value,
+            bits,
+            barrett_ratio,
+            montgomery_inv: inv.wrapping_neg(),
+            montgomery_r2,
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    ///
+    /// Reduce any u64 into [0, p)
+    ///
+    pub fn reduce(&self, a: u64) -> u64 {
+        self.reduce_u128(a as u128)
+    }
+
+    ///
+    /// Reduce a value below p^2 (e.g. a product) into [0, p) with Barrett reduction. Larger
+    /// values are reduced with a division.
+    ///
+    pub fn reduce_u128(&self, a: u128) -> u64 {
+        let p = self.value as u128;
+        if a >= p * p {
+            return (a % p) as u64;
+        }
+        // Underestimates a / p by at most 2
+        let k = self.bits;
+        let q = ((a >> (k - 1)) * self.barrett_ratio as u128) >> (k + 1);
+        let mut r = (a - q * p) as u64;
+        while r >= self.value {
+            r -= self.value;
+        }
+        r
+    }
+
+    ///
+    /// Reduce an i64 into [0, p)
+    ///
+    pub fn reduce_i64(&self, a: i64) -> u64 {
+        let r = self.reduce(a.unsigned_abs());
+        self.neg_mod_if(r, a < 0)
+    }
+
+    ///
+    /// Reduce a BigInt into [0, p)
+    ///
+    pub fn reduce_bigint(&self, a: &BigInt) -> u64 {
+        let r = (a % self.value).abs().to_u64().unwrap();
+        self.neg_mod_if(r, a.is_negative())
+    }
+
+    fn neg_mod_if(&self, a: u64, negate: bool) -> u64 {
+        if negate {
+            self.neg_mod(a)
+        } else {
+            a
+        }
+    }
+
+    ///
+    /// Map a value in [0, p) to the centered representative in (-p/2, p/2]
+    ///
+    pub fn center(&self, a: u64) -> i64 {
+        if a > self.value / 2 {
+            a as i64 - self.value as i64
+        } else {
+            a as i64
+        }
+    }
+
+    pub fn add_mod(&self, a: u64, b: u64) -> u64 {
+        let sum = a + b;
+        if sum >= self.value {
+            sum - self.value
+        } else {
+            sum
+        }
+    }
+
+    pub fn sub_mod(&self, a: u64, b: u64) -> u64 {
+        if a >= b {
+            a - b
+        } else {
+            a + self.value - b
+        }
+    }
+
+    pub fn neg_mod(&self, a: u64) -> u64 {
+        if a == 0 {
+            0
+        } else {
+            self.value - a
+        }
+    }
+
+    pub fn mul_mod(&self, a: u64, b: u64) -> u64 {
+        self.reduce_u128(a as u128 * b as u128)
+    }
+
+    pub fn pow_mod(&self, a: u64, mut b: u64) -> u64 {
+        if self.value & 1 == 0 {
+            let (mut base, mut res) = (self.reduce(a), self.reduce(1));
+            while b != 0 {
+                if b & 1 == 1 {
+                    res = self.mul_mod(res, base);
+                }
+                base = self.mul_mod(base, base);
+                b >>= 1;
+            }
+            return res;
+        }
+
+        let mut base = self.to_montgomery(a);
+        let mut res = self.to_montgomery(1);
+        while b != 0 {
+            if b & 1 == 1 {
+                res = self.mul_montgomery(res, base);
+            }
+            base = self.mul_montgomery(base, base);
+            b >>= 1;
+        }
+        self.from_montgomery(res)
+    }
+
+    ///
+    /// The inverse of a modulo p, if it exists
+    ///
+    pub fn inv_mod(&self, a: u64) -> Option<u64> {
+        // Extended Euclid, tracking only the coefficient of a
+        let (mut r0, mut r1) = (self.value as i128, self.reduce(a) as i128);
+        let (mut t0, mut t1) = (0i128, 1i128);
+        while r1 != 0 {
+            let q = r0 / r1;
+            (r0, r1) = (r1, r0 - q * r1);
+            (t0, t1) = (t1, t0 - q * t1);
+        }
+        if r0 != 1 {
+            return None;
+        }
+        Some(t0.rem_euclid(self.value as i128) as u64)
+    }
+
+    ///
+    /// floor(w * 2^64 / p), to multiply by a fixed w with `mul_shoup`
+    ///
+    pub fn shoup(&self, w: u64) -> u64 {
+        (((w as u128) << 64) / self.value as u128) as u64
+    }
+
+    ///
+    /// a * w mod p in [0, 2p) for any a, given w < p and w_shoup = shoup(w)
+    ///
+    #[inline]
+    pub fn mul_shoup_lazy(&self, a: u64, w: u64, w_shoup: u64) -> u64 {
+        let q = ((a as u128 * w_shoup as u128) >> 64) as u64;
+        a.wrapping_mul(w).wrapping_sub(q.wrapping_mul(self.value))
+    }
+
+    ///
+    /// a * w mod p in [0, p) for any a, given w < p and w_shoup = shoup(w)
+    ///
+    #[inline]
+    pub fn mul_shoup(&self, a: u64, w: u64, w_shoup: u64) -> u64 {
+        let r = self.mul_shoup_lazy(a, w, w_shoup);
+        if r >= self.value {
+            r - self.value
+        } else {
+            r
+        }
+    }
+
+    ///
+    /// a * 2^64 mod p
+    ///
+    pub fn to_montgomery(&self, a: u64) -> u64 {
+        self.mul_montgomery(a, self.montgomery_r2)
+    }
+
+    ///
+    /// a * 2^-64 mod p
+    ///
+    pub fn from_montgomery(&self, a: u64) -> u64 {
+        self.montgomery_reduce(a as u128)
+    }
+
+    ///
+    /// a * b * 2^-64 mod p, so the product of two values in Montgomery form stays in that form.
+    /// Only works for odd moduli.
+    ///
+    pub fn mul_montgomery(&self, a: u64, b: u64) -> u64 {
+        self.montgomery_reduce(a as u128 * b as u128)
+    }
+
+    fn montgomery_reduce(&self, t: u128) -> u64 {
+        let m = (t as u64).wrapping_mul(self.montgomery_inv);
+        let u = ((t + m as u128 * self.value as u128) >> 64) as u64;
+        if u >= self.value {
+            u - self.value
+        } else {
+            u
+        }
+    }
+
+    ///
+    /// a[i] = a[i] + b[i] mod p
+    ///
+    pub fn add_vec(&self, a: &mut [u64], b: &[u64]) {
+        assert_eq!(a.len(), b.len());
+        a.iter_mut()
+            .zip(b)
+            .for_each(|(x, &y)| *x = self.add_mod(*x, y));
+    }
+
+    ///
+    /// a[i] = a[i] - b[i] mod p
+    ///
+    pub fn sub_vec(&self, a: &mut [u64], b: &[u64]) {
+        assert_eq!(a.len(), b.len());
+        a.iter_mut()
+            .zip(b)
+            .for_each(|(x, &y)| *x = self.sub_mod(*x, y));
+    }
+
+    ///
+    /// a[i] = a[i] * b[i] mod p
+    ///
+    pub fn mul_vec(&self, a: &mut [u64], b: &[u64]) {
+        assert_eq!(a.len(), b.len());
+        a.iter_mut()
+            .zip(b)
+            .for_each(|(x, &y)| *x = self.mul_mod(*x, y));
+    }
+
+    ///
+    /// a[i] = -a[i] mod p
+    ///
+    pub fn neg_vec(&self, a: &mut [u64]) {
+        a.iter_mut().for_each(|x| *x = self.neg_mod(*x));
+    }
+
+    ///
+    /// a[i] = a[i] * scalar mod p
+    ///
+    pub fn scalar_mul_vec(&self, a: &mut [u64], scalar: u64) {
+        let scalar = self.reduce(scalar);
+        let scalar_shoup = self.shoup(scalar);
+        a.iter_mut()
+            .for_each(|x| *x = self.mul_shoup(*x, scalar, scalar_shoup));
+    }
+
+    ///
+    /// a[i] = a[i] mod p, for arbitrary u64 values
+    ///
+    pub fn reduce_vec(&self, a: &mut [u64]) {
+        a.iter_mut().for_each(|x| *x = self.reduce(*x));
+    }
+
+    ///
+    /// Map signed values into [0, p)
+    ///
+    pub fn from_centered(&self, a: &[i64]) -> Vec<u64> {
+        a.iter().map(|&x| self.reduce_i64(x)).collect()
+    }
+
+    ///
+    /// Map values in [0, p) to their centered representatives in (-p/2, p/2]
+    ///
+    pub fn to_centered(&self, a: &[u64]) -> Vec<i64> {
+        a.iter().map(|&x| self.center(x)).collect()
+    }
+}
+
+#[test]
+fn modulus_test() {
+    let primes = [7u64, 73, 1 << 31 | 1 << 27 | 1, 1152921504606748673];
+
+    for &p in primes.iter() {
+        let m = Modulus::new(p);
+        let values = [0, 1, 2, p / 3, p / 2, p - 2, p - 1];
+
+        for &a in values.iter() {
+            for &b in values.iter() {
+                let (a128, b128, p128) = (a as u128, b as u128, p as u128);
+                assert_eq!(m.add_mod(a, b) as u128, (a128 + b128) % p128);
+                assert_eq!(m.sub_mod(a, b) as u128, (a128 + p128 - b128) % p128);
+                assert_eq!(m.mul_mod(a, b) as u128, a128 * b128 % p128);
+                assert_eq!(m.mul_shoup(a, b, m.shoup(b)) as u128, a128 * b128 % p128);
+                let (am, bm) = (m.to_montgomery(a), m.to_montgomery(b));
+                assert_eq!(
+                    m.from_montgomery(m.mul_montgomery(am, bm)) as u128,
+                    a128 * b128 % p128
+                );
+            }
+            if a != 0 {
+                let inv = m.inv_mod(a).unwrap();
+                assert_eq!(m.mul_mod(a, inv), 1);
+                assert_eq!(m.pow_mod(a, p - 1), 1);
+            }
+            assert_eq!(m.reduce_i64(m.center(a)), a);
+        }
+
+        assert_eq!(m.reduce_u128(u128::MAX), (u128::MAX % p as u128) as u64);
+        assert_eq!(m.reduce_i64(-1), p - 1);
+        assert_eq!(m.reduce_bigint(&BigInt::from(-1)), p - 1);
+    }
+
+    assert_eq!(Modulus::new(12).inv_mod(4), None);
+    assert_eq!(Modulus::new(12).inv_mod(5), Some(5));
+    assert_eq!(Modulus::new(12).pow_mod(5, 3), 5);
+}
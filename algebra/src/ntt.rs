@@ -1,112 +1,200 @@
-use crate::utils::{bit_reverse_vec, invmod, root_of_unity, RemEuclid};
-use num_bigint::BigInt;
-use num_traits::One;
+use crate::modulus::Modulus;
+use crate::utils::reverse_bits;
 
 ///
-/// Parameters for Number/Fermat Theoretic Transform
+/// Parameters for the negacyclic Number Theoretic Transform over Z_p[X]/(X^N + 1)
+///
+/// Everything works on native u64 values. Twiddle factors are stored with their Shoup
+/// precomputation (see `Modulus::shoup`), so a multiplication by a twiddle costs two word
+/// multiplications and no division, and the butterflies are Harvey's lazy butterflies that keep
+/// values in [0, 4p) and only fully reduce at the end. This requires p < 2^62.
 ///
 #[derive(Debug)]
 pub struct Ntt {
-    coeff_modulus: u64,
+    modulus: Modulus,
     degree: u64,
-    pub roots_of_unity: Vec<BigInt>,
-    pub roots_of_unity_inv: Vec<BigInt>,
+    // psi^bitrev(i) for a primitive 2N-th root of unity psi
+    roots: Vec<u64>,
+    roots_shoup: Vec<u64>,
+    // psi^-bitrev(i)
+    roots_inv: Vec<u64>,
+    roots_inv_shoup: Vec<u64>,
+    degree_inv: u64,
+    degree_inv_shoup: u64,
+}
+
+///
+/// Find a primitive 2N-th root of unity modulo a prime p = 1 (mod 2N).
+///
+/// Since 2N is a power of two, psi is primitive exactly when psi^N = -1.
+///
+fn primitive_root(degree: u64, modulus: &Modulus) -> u64 {
+    let p = modulus.value();
+    let order = 2 * degree;
+    assert_eq!(
+        (p - 1) % order,
+        0,
+        "Modulus needs to be 1 mod 2N to have a 2N-th root of unity"
+    );
+    (2..p)
+        .map(|x| modulus.pow_mod(x, (p - 1) / order))
+        .find(|&psi| modulus.pow_mod(psi, degree) == p - 1)
+        .expect("Modulus needs to be prime")
 }
 
 impl Ntt {
     pub fn new(degree: u64, coeff_modulus: u64) -> Self {
         assert!(
-            degree & (degree - 1) == 0,
+            degree.is_power_of_two(),
             "Polynomial degree needs to be a power of 2"
         );
+        let modulus = Modulus::new(coeff_modulus);
+        let log_degree = degree.trailing_zeros();
 
-        let root_of_unity = root_of_unity(2 * degree as i128, coeff_modulus as i128);
-        let root_of_unity_inv = invmod(&root_of_unity, coeff_modulus as i128);
-
-        let mut roots_of_unity = vec![One::one(); degree as usize];
-        let mut roots_of_unity_inv = vec![One::one(); degree as usize];
+        let psi = primitive_root(degree, &modulus);
+        let psi_inv = modulus.inv_mod(psi).unwrap();
 
+        let mut powers = vec![1u64; degree as usize];
+        let mut powers_inv = vec![1u64; degree as usize];
         for i in 1..degree as usize {
-            roots_of_unity[i] = (&roots_of_unity[i - 1] * &root_of_unity) % coeff_modulus;
+            powers[i] = modulus.mul_mod(powers[i - 1], psi);
+            powers_inv[i] = modulus.mul_mod(powers_inv[i - 1], psi_inv);
         }
 
-        for i in 1..degree as usize {
-            roots_of_unity_inv[i] =
-                (&roots_of_unity_inv[i - 1] * &root_of_unity_inv) % coeff_modulus;
-        }
+        let roots: Vec<u64> = (0..degree as usize)
+            .map(|i| powers[reverse_bits(i, log_degree)])
+            .collect();
+        let roots_inv: Vec<u64> = (0..degree as usize)
+            .map(|i| powers_inv[reverse_bits(i, log_degree)])
+            .collect();
+
+        let roots_shoup = roots.iter().map(|&w| modulus.shoup(w)).collect();
+        let roots_inv_shoup = roots_inv.iter().map(|&w| modulus.shoup(w)).collect();
+
+        let degree_inv = modulus.inv_mod(degree).unwrap();
 
         Ntt {
-            coeff_modulus,
+            modulus,
             degree,
-            roots_of_unity,
-            roots_of_unity_inv,
+            roots,
+            roots_shoup,
+            roots_inv,
+            roots_inv_shoup,
+            degree_inv,
+            degree_inv_shoup: modulus.shoup(degree_inv),
         }
     }
 
-    pub fn ntt(&self, coeffs: &Vec<BigInt>, rou: &Vec<BigInt>) -> Vec<BigInt> {
-        assert!(
-            coeffs.len() == coeffs.len(),
-            "Length of roots of unity is too small"
-        );
-
-        let num_coeffs = coeffs.len();
-        let mut result = bit_reverse_vec(coeffs);
-
-        let log_num_coeffs = num_coeffs.log2();
+    pub fn coeff_modulus(&self) -> u64 {
+        self.modulus.value()
+    }
 
-        for logm in 1..log_num_coeffs + 1 {
-            for j in (0..num_coeffs).step_by(1 << logm) {
-                for i in 0..(1 << (logm - 1)) {
-                    let index_even = j + i;
-                    let index_odd = j + i + (1 << (logm - 1));
+    pub fn modulus(&self) -> &Modulus {
+        &self.modulus
+    }
 
-                    let rou_idx = i << (1 + log_num_coeffs - logm);
-                    let omega_factor =
-                        (&rou[rou_idx as usize] * &result[index_odd]) % self.coeff_modulus;
+    pub fn degree(&self) -> u64 {
+        self.degree
+    }
 
-                    let butterfly_plus =
-                        (&result[index_even] + &omega_factor).rem_euclid(&self.coeff_modulus);
-                    let butterfly_minus =
-                        (&result[index_even] - &omega_factor).rem_euclid(&self.coeff_modulus);
+    ///
+    /// Forward transform in place. Input coefficients need to be in [0, p) and the output is in
+    /// bit-reversed order, also in [0, p).
+    ///
+    pub fn forward(&self, values: &mut [u64]) {
+        let n = self.degree as usize;
+        assert_eq!(
+            values.len(),
+            n,
+            "forward: input length does not match degree"
+        );
 
-                    result[index_even] = butterfly_plus;
-                    result[index_odd] = butterfly_minus;
+        let p = self.modulus.value();
+        let two_p = 2 * p;
+
+        let mut t = n;
+        let mut m = 1;
+        while m < n {
+            t >>= 1;
+            for i in 0..m {
+                let w = self.roots[m + i];
+                let w_shoup = self.roots_shoup[m + i];
+
+                let (lo, hi) = values[2 * i * t..2 * (i + 1) * t].split_at_mut(t);
+                for (x, y) in lo.iter_mut().zip(hi.iter_mut()) {
+                    let mut u = *x;
+                    if u >= two_p {
+                        u -= two_p;
+                    }
+                    let v = self.modulus.mul_shoup_lazy(*y, w, w_shoup);
+                    *x = u + v;
+                    *y = u + two_p - v;
                 }
             }
+            m <<= 1;
         }
-        result
-    }
 
-    pub fn fft_fwd(&self, coeffs: &Vec<BigInt>) -> Vec<BigInt> {
-        let num_coeffs = coeffs.len();
+        for x in values.iter_mut() {
+            if *x >= two_p {
+                *x -= two_p;
+            }
+            if *x >= p {
+                *x -= p;
+            }
+        }
+    }
 
-        assert!(
-            num_coeffs == self.degree as usize,
-            "fft_fwd: input length does not match degree"
+    ///
+    /// Inverse transform in place. Input values need to be in [0, 2p) and in bit-reversed order,
+    /// and the output coefficients are in natural order in [0, p).
+    ///
+    pub fn inverse(&self, values: &mut [u64]) {
+        let n = self.degree as usize;
+        assert_eq!(
+            values.len(),
+            n,
+            "inverse: input length does not match degree"
         );
 
-        let fft_input: Vec<BigInt> = (0..num_coeffs)
-            .map(|i| (&coeffs[i] * &self.roots_of_unity[i]) % self.coeff_modulus)
-            .collect();
+        let p = self.modulus.value();
+        let two_p = 2 * p;
+
+        let mut t = 1;
+        let mut m = n >> 1;
+        while m > 0 {
+            for i in 0..m {
+                let w = self.roots_inv[m + i];
+                let w_shoup = self.roots_inv_shoup[m + i];
+
+                let (lo, hi) = values[2 * i * t..2 * (i + 1) * t].split_at_mut(t);
+                for (x, y) in lo.iter_mut().zip(hi.iter_mut()) {
+                    let u = *x;
+                    let v = *y;
+                    let mut sum = u + v;
+                    if sum >= two_p {
+                        sum -= two_p;
+                    }
+                    *x = sum;
+                    *y = self.modulus.mul_shoup_lazy(u + two_p - v, w, w_shoup);
+                }
+            }
+            t <<= 1;
+            m >>= 1;
+        }
 
-        self.ntt(&fft_input, &self.roots_of_unity)
+        for x in values.iter_mut() {
+            *x = self
+                .modulus
+                .mul_shoup(*x, self.degree_inv, self.degree_inv_shoup);
+        }
     }
 
-    pub fn fft_inv(&self, coeffs: &Vec<BigInt>) -> Vec<BigInt> {
-        let num_coeffs = coeffs.len();
-        assert!(
-            num_coeffs == self.degree as usize,
-            "fft_inv: input length does not match degree"
-        );
-
-        let to_scale_down = self.ntt(coeffs, &self.roots_of_unity_inv);
-        let poly_degree_inv = invmod(&(self.degree as i128), self.coeff_modulus as i128);
-
-        (0..num_coeffs)
-            .map(|i| {
-                (&to_scale_down[i] * &self.roots_of_unity_inv[i] * poly_degree_inv)
-                    % self.coeff_modulus as i128
-            })
-            .collect()
+    ///
+    /// Multiply two transformed polynomials slot by slot, storing the result in `a`.
+    ///
+    /// Inputs need to be in [0, p).
+    ///
+    pub fn pointwise_mul(&self, a: &mut [u64], b: &[u64]) {
+        self.modulus.mul_vec(a, b);
     }
 }
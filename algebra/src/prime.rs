@@ -0,0 +1,167 @@
+///
+/// Deterministic Miller-Rabin primality test for any u64.
+///
+/// Testing against the first 12 primes as witnesses is enough for every n < 3.3 * 10^24, so no
+/// randomness is involved.
+///
+pub fn is_prime(n: u64) -> bool {
+    const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+    if n < 2 {
+        return false;
+    }
+    for &p in WITNESSES.iter() {
+        let rem = n % p;
+        if rem == 0 {
+            return n == p;
+        }
+    }
+
+    let mul_mod = |a: u64, b: u64| (a as u128 * b as u128 % n as u128) as u64;
+    let pow_mod = |mut a: u64, mut b: u64| {
+        let mut res = 1;
+        while b != 0 {
+            if b & 1 == 1 {
+                res = mul_mod(res, a);
+            }
+            a = mul_mod(a, a);
+            b >>= 1;
+        }
+        res
+    };
+
+    // n - 1 = d * 2^s with d odd
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+
+    WITNESSES.iter().all(|&a| {
+        let mut x = pow_mod(a, d);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+///
+/// Iterates over the NTT-friendly primes for polynomials of a given degree N, i.e. primes
+/// p = 1 (mod 2N), ordered by their distance to 2^bits. Candidates are taken from both sides of
+/// 2^bits, so the primes are as close as possible to the power of two, which keeps the error of
+/// dividing by them (e.g. when rescaling) small.
+///
+#[derive(Debug, Clone)]
+pub struct NttPrimes {
+    target: u64,
+    order: u64,
+    // Next candidates above and below the target, None once exhausted
+    up: Option<u64>,
+    down: Option<u64>,
+}
+
+impl NttPrimes {
+    pub fn new(bits: u32, degree: u64) -> Self {
+        assert!(
+            degree.is_power_of_two(),
+            "Polynomial degree needs to be a power of 2"
+        );
+        assert!(bits < 62, "Primes need to be below 2^62");
+
+        let order = 2 * degree;
+        assert!(
+            order < 1 << bits,
+            "2^bits needs to be larger than 2N to find primes = 1 (mod 2N)"
+        );
+
+        let target = 1u64 << bits;
+        NttPrimes {
+            target,
+            order,
+            up: Some(target + 1),
+            down: Some(target - order + 1),
+        }
+    }
+}
+
+impl Iterator for NttPrimes {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        loop {
+            let candidate = match (self.up, self.down) {
+                (Some(up), Some(down)) if self.target - down < up - self.target => {
+                    self.down = down.checked_sub(self.order).filter(|&p| p > 1);
+                    down
+                }
+                (Some(up), _) => {
+                    self.up = Some(up + self.order).filter(|&p| p < 1 << 62);
+                    up
+                }
+                (None, Some(down)) => {
+                    self.down = down.checked_sub(self.order).filter(|&p| p > 1);
+                    down
+                }
+                (None, None) => return None,
+            };
+            if is_prime(candidate) {
+                return Some(candidate);
+            }
+        }
+    }
+}
+
+///
+/// Select one distinct NTT-friendly prime for polynomials of the given degree per entry in
+/// `bit_sizes`, each as close as possible to 2^bits.
+///
+/// Primes are picked in the order of `bit_sizes`, so when several entries share a bit size, the
+/// first entry gets the closest prime, the second entry the next closest one and so on.
+///
+pub fn ntt_primes(bit_sizes: &[u32], degree: u64) -> Vec<u64> {
+    let mut primes: Vec<u64> = Vec::with_capacity(bit_sizes.len());
+    for &bits in bit_sizes {
+        let prime = NttPrimes::new(bits, degree)
+            .find(|p| !primes.contains(p))
+            .expect("ntt_primes: ran out of primes");
+        primes.push(prime);
+    }
+    primes
+}
+
+#[test]
+fn is_prime_test() {
+    let primes = [
+        2u64,
+        3,
+        5,
+        37,
+        41,
+        65537,
+        1152921504606748673,
+        18446744073709551557,
+    ];
+    let composites = [
+        0u64,
+        1,
+        4,
+        9,
+        1373653,
+        25326001,
+        3215031751,
+        3825123056546413051,
+    ];
+
+    assert!(primes.iter().all(|&p| is_prime(p)));
+    assert!(composites.iter().all(|&c| !is_prime(c)));
+
+    // Agrees with trial division on small numbers
+    for n in 0..2000u64 {
+        let trial = n >= 2 && (2..n).take_while(|d| d * d <= n).all(|d| n % d != 0);
+        assert_eq!(is_prime(n), trial, "{}", n);
+    }
+}
@@ -1,5 +1,25 @@
 use algebra::ntt::*;
-use num_bigint::{BigInt, ToBigInt};
+use algebra::utils::reverse_bits;
+
+///
+/// Negacyclic product of a and b modulo p, the schoolbook way
+///
+fn negacyclic_mul(a: &[u64], b: &[u64], p: u64) -> Vec<u64> {
+    let n = a.len();
+    let mut res = vec![0u128; n];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            let prod = x as u128 * y as u128 % p as u128;
+            let k = (i + j) % n;
+            if i + j < n {
+                res[k] = (res[k] + prod) % p as u128;
+            } else {
+                res[k] = (res[k] + p as u128 - prod) % p as u128;
+            }
+        }
+    }
+    res.iter().map(|&x| x as u64).collect()
+}
 
 #[test]
 fn ntt_test() {
@@ -7,19 +27,19 @@ fn ntt_test() {
     let coeff_modulus = 73;
     let ntt = Ntt::new(poly_degree, coeff_modulus);
 
-    let input: Vec<BigInt> = vec![0, 1, 4, 5]
-        .iter()
-        .map(|x| x.to_bigint().unwrap())
-        .collect();
-
-    let fwd = ntt.ntt(&input, &ntt.roots_of_unity);
+    let input = vec![0, 1, 4, 5];
+    let mut fwd = input.clone();
+    ntt.forward(&mut fwd);
 
-    let expected: Vec<BigInt> = vec![10, 34, 71, 31]
-        .iter()
-        .map(|x| x.to_bigint().unwrap())
-        .collect();
-
-    assert_eq!(expected, fwd);
+    // Slot i holds the evaluation at psi^(2 * bitrev(i) + 1) for some primitive 8th root psi
+    let psi = (2..coeff_modulus)
+        .find(|&psi| {
+            let eval = |x: u64| input.iter().rev().fold(0, |acc, c| (acc * x + c) % 73);
+            let pow = |k: usize| (0..k).fold(1, |acc, _| acc * psi % 73);
+            pow(4) == 72 && (0..4).all(|i| fwd[i] == eval(pow(2 * reverse_bits(i, 2) + 1)))
+        })
+        .is_some();
+    assert!(psi);
 }
 
 #[test]
@@ -28,16 +48,168 @@ fn ntt_inv_test() {
     let coeff_modulus = 73;
     let ntt = Ntt::new(poly_degree, coeff_modulus);
 
-    let input: Vec<BigInt> = vec![10, 34, 71, 31]
-        .iter()
-        .map(|x| (x * -18i128).rem_euclid(73).to_bigint().unwrap())
-        .collect();
+    let input = vec![0, 1, 4, 5];
+    let mut values = input.clone();
+    ntt.forward(&mut values);
+    ntt.inverse(&mut values);
+    assert_eq!(input, values);
+}
+
+#[test]
+fn ntt_mul_test() {
+    let poly_degree = 64;
+    // Primes = 1 (mod 128): one of 60 bits, and the small 769 and 257
+    for coeff_modulus in [1152921504606748673u64, 769, 257] {
+        let ntt = Ntt::new(poly_degree, coeff_modulus);
+
+        let a: Vec<u64> = (0..poly_degree)
+            .map(|i| (i * i * 7919 + 13) % coeff_modulus)
+            .collect();
+        let b: Vec<u64> = (0..poly_degree)
+            .map(|i| coeff_modulus - 1 - (i * 104729) % coeff_modulus)
+            .collect();
 
-    let inv = ntt.ntt(&input, &ntt.roots_of_unity_inv);
+        let mut a_ntt = a.clone();
+        let mut b_ntt = b.clone();
+        ntt.forward(&mut a_ntt);
+        ntt.forward(&mut b_ntt);
+        ntt.pointwise_mul(&mut a_ntt, &b_ntt);
+        ntt.inverse(&mut a_ntt);
+
+        assert_eq!(negacyclic_mul(&a, &b, coeff_modulus), a_ntt);
+    }
+}
 
-    let expected: Vec<BigInt> = vec![0, 1, 4, 5]
-        .iter()
-        .map(|x| x.to_bigint().unwrap())
-        .collect();
-    assert_eq!(expected, inv);
+#[test]
+fn crt_poly_test() {
+    use algebra::crt::Crt;
+    use num_bigint::BigInt;
+    use num_traits::One;
+
+    let crt = Crt::new(24, 30, 8);
+
+    // Values up to the full modulus, far above 2^127
+    let coef: Vec<BigInt> = vec![
+        BigInt::from(0),
+        BigInt::from(1),
+        BigInt::from(-1),
+        BigInt::one() << 500,
+        BigInt::from(-1) << 600,
+        &crt.modulus - 1u32,
+        BigInt::from(u64::MAX) * BigInt::from(u64::MAX) * 12345u32,
+    ];
+
+    let residues = crt.decompose_poly(&coef);
+    assert_eq!(residues.len(), 24);
+    for (r, &p) in residues.iter().zip(crt.primes()) {
+        assert!(r.iter().all(|&x| x < p));
+    }
+
+    let reconstructed = crt.reconstruct_poly(&residues);
+    for (c, r) in coef.iter().zip(&reconstructed) {
+        let expected = ((c % &crt.modulus) + &crt.modulus) % &crt.modulus;
+        assert_eq!(&expected, r);
+    }
+
+    assert_eq!(
+        crt.decompose(&coef[3]),
+        residues.iter().map(|r| r[3]).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn base_conversion_test() {
+    use algebra::crt::{BaseConverter, Crt, ExtendedBasis};
+    use num_bigint::BigInt;
+    use num_traits::{One, Zero};
+
+    let q = Crt::new(3, 30, 8);
+    let p = Crt::new(3, 40, 8);
+
+    let center = |x: &BigInt, m: &BigInt| {
+        let x = ((x % m) + m) % m;
+        if &x * 2 >= *m {
+            x - m
+        } else {
+            x
+        }
+    };
+
+    let coef: Vec<BigInt> = vec![
+        BigInt::zero(),
+        BigInt::one(),
+        BigInt::from(-1),
+        &q.modulus / 2u32 - (1u64 << 40),
+        -(&q.modulus / 2u32) + (1u64 << 40),
+        &q.modulus / 3u32,
+        BigInt::from(123456789u64) * 1000000007u64,
+    ];
+
+    // Exact conversion gives the centered value modulo P
+    let q_to_p = BaseConverter::new(&q, &p);
+    let exact = q_to_p.exact_convert(&q.decompose_poly(&coef));
+    let expected: Vec<BigInt> = coef.iter().map(|c| center(c, &q.modulus)).collect();
+    assert_eq!(exact, p.decompose_poly(&expected));
+
+    // Fast conversion is off by a small multiple of Q
+    let fast = q_to_p.fast_convert(&q.decompose_poly(&coef));
+    let fast = p.reconstruct_poly(&fast);
+    for (c, f) in coef.iter().zip(&fast) {
+        let c = ((c % &q.modulus) + &q.modulus) % &q.modulus;
+        let diff = f - c;
+        assert!((0..3u32).any(|v| diff == &q.modulus * v));
+    }
+
+    // ModUp keeps the value, ModDown divides by P and rounds
+    let basis = ExtendedBasis::new(&q, &p);
+
+    let up = basis.mod_up(&q.decompose_poly(&coef));
+    assert_eq!(up.len(), 6);
+    assert_eq!(up[..3], q.decompose_poly(&expected)[..]);
+    assert_eq!(up[3..], p.decompose_poly(&expected)[..]);
+
+    let big: Vec<BigInt> = expected.iter().map(|c| c * &p.modulus + 17u32).collect();
+    let mut big_residues = q.decompose_poly(&big);
+    big_residues.extend(p.decompose_poly(&big));
+    let down = basis.mod_down(&big_residues);
+    assert_eq!(down, q.decompose_poly(&expected));
+}
+
+#[test]
+fn ntt_primes_test() {
+    use algebra::crt::Crt;
+    use algebra::prime::{is_prime, ntt_primes, NttPrimes};
+    use num_bigint::BigInt;
+
+    let degree = 1 << 10;
+
+    // The closest primes to 2^40 come from both sides
+    let closest: Vec<u64> = NttPrimes::new(40, degree).take(6).collect();
+    assert!(closest.iter().any(|&p| p < 1 << 40));
+    assert!(closest.iter().any(|&p| p > 1 << 40));
+    let distances: Vec<u64> = closest.iter().map(|&p| p.abs_diff(1 << 40)).collect();
+    assert!(distances.windows(2).all(|d| d[0] < d[1]));
+
+    let bit_sizes = [60, 40, 40, 40, 50];
+    let primes = ntt_primes(&bit_sizes, degree);
+    assert_eq!(primes[1..4], closest[..3]);
+    for (i, (&p, &bits)) in primes.iter().zip(&bit_sizes).enumerate() {
+        assert!(is_prime(p));
+        assert_eq!(p % (2 * degree), 1);
+        assert!(p.abs_diff(1 << bits) < 1 << (bits - 10));
+        assert!(!primes[..i].contains(&p));
+    }
+
+    let crt = Crt::from_primes(&primes, degree);
+    assert_eq!(crt.primes(), &primes[..]);
+    let value = BigInt::from(-123456789i64) << 150;
+    let residues = crt.decompose(&value);
+    let restored = crt.reconstruct_poly(&residues.iter().map(|&r| vec![r]).collect::<Vec<_>>());
+    assert_eq!(restored[0], value + &crt.modulus);
+}
+
+#[test]
+#[should_panic]
+fn crt_duplicate_primes_test() {
+    algebra::crt::Crt::from_primes(&[12289, 12289], 1024);
 }
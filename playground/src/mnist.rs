@@ -67,7 +67,7 @@ fn main() {
     };
     let plain = encode(&padded_message, scaling_factor, &encoder);
     let cipher = encrypt(key.public(), &ciph_modulus, &plain);
-    let out = decrypt(key.private(), cipher);
+    let out = decrypt(key.private(), &cipher);
     let img = decode(out, &encoder);
 
     let mut img: Vec<u8> = img.iter().map(|x| (x.re * 255.) as u8).collect();
@@ -0,0 +1,279 @@
+use crate::Polynomial;
+
+use std::f64::consts::PI;
+
+// Points per unit of degree on which the error of an approximation is measured
+const GRID_DENSITY: usize = 200;
+
+// Remez iterations stop once the levelled error is this close to the max error, relatively
+const REMEZ_TOLERANCE: f64 = 1e-9;
+const REMEZ_MAX_ITERATIONS: usize = 100;
+
+///
+/// A polynomial approximation of a function on [a, b], kept in the Chebyshev basis of
+/// t = (2x - a - b) / (b - a), which is numerically stable, and convertible to the power basis
+/// that homomorphic evaluation with plain multiplications needs.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Approximation {
+    a: f64,
+    b: f64,
+    // Coefficients of T_0, ..., T_n
+    chebyshev: Vec<f64>,
+    max_error: f64,
+}
+
+impl Approximation {
+    ///
+    /// Interpolate f at the degree + 1 Chebyshev nodes of [a, b], which is close to the best
+    /// approximation for smooth functions
+    ///
+    pub fn chebyshev<F: Fn(f64) -> f64>(f: F, a: f64, b: f64, degree: usize) -> Self {
+        assert!(a < b, "approximation: the interval needs a < b");
+        let n = degree + 1;
+        let nodes: Vec<f64> = (0..n)
+            .map(|j| (PI * (j as f64 + 0.5) / n as f64).cos())
+            .collect();
+        let values: Vec<f64> = nodes.iter().map(|&t| f(from_unit(t, a, b))).collect();
+
+        let chebyshev = (0..n)
+            .map(|k| {
+                let sum: f64 = nodes
+                    .iter()
+                    .zip(&values)
+                    .map(|(&t, y)| y * (k as f64 * t.acos()).cos())
+                    .sum();
+                let c = 2. * sum / n as f64;
+                if k == 0 {
+                    c / 2.
+                } else {
+                    c
+                }
+            })
+            .collect();
+
+        Self::with_error(&f, a, b, chebyshev)
+    }
+
+    ///
+    /// The minimax approximation of f of the given degree on [a, b], i.e. with the smallest max
+    /// error, with the Remez exchange algorithm: solve for the polynomial whose error alternates
+    /// with equal magnitude on degree + 2 reference points, then move the references to the
+    /// extrema of the error, until these are level.
+    ///
+    /// Starts from the Chebyshev interpolant, and returns the best approximation found if the
+    /// exchange does not converge (e.g. for discontinuous functions).
+    ///
+    pub fn remez<F: Fn(f64) -> f64>(f: F, a: f64, b: f64, degree: usize) -> Self {
+        let mut best = Self::chebyshev(&f, a, b, degree);
+        let n = degree + 2;
+
+        // Chebyshev extrema, where the error of the interpolant roughly alternates
+        let mut refs: Vec<f64> = (0..n)
+            .map(|i| -(PI * i as f64 / (n - 1) as f64).cos())
+            .collect();
+
+        for _ in 0..REMEZ_MAX_ITERATIONS {
+            // sum_k c_k T_k(t_i) + (-1)^i E = f(x_i)
+            let matrix = refs
+                .iter()
+                .enumerate()
+                .map(|(i, &t)| {
+                    let mut row = chebyshev_basis(t, degree + 1);
+                    row.push(if i & 1 == 0 { 1. } else { -1. });
+                    row
+                })
+                .collect();
+            let rhs = refs.iter().map(|&t| f(from_unit(t, a, b))).collect();
+            let mut sol = match solve(matrix, rhs) {
+                Some(sol) => sol,
+                None => break,
+            };
+            let levelled = sol.pop().unwrap().abs();
+
+            let approx = Self::with_error(&f, a, b, sol);
+            if approx.max_error < best.max_error {
+                best = approx.clone();
+            }
+            if approx.max_error - levelled <= REMEZ_TOLERANCE * approx.max_error {
+                break;
+            }
+            refs = match approx.extrema(&f, n) {
+                Some(refs) => refs,
+                None => break,
+            };
+        }
+        best
+    }
+
+    fn with_error<F: Fn(f64) -> f64>(f: &F, a: f64, b: f64, chebyshev: Vec<f64>) -> Self {
+        let mut res = Self {
+            a,
+            b,
+            chebyshev,
+            max_error: 0.,
+        };
+        res.max_error = grid(res.degree())
+            .map(|t| (f(from_unit(t, a, b)) - res.eval_unit(t)).abs())
+            .fold(0., f64::max);
+        res
+    }
+
+    ///
+    /// Points of [-1, 1] where the error alternates in sign and is largest in magnitude, one per
+    /// interval of constant sign, reduced to n points by dropping the smaller of the end ones
+    ///
+    fn extrema<F: Fn(f64) -> f64>(&self, f: &F, n: usize) -> Option<Vec<f64>> {
+        let error = |t: f64| f(from_unit(t, self.a, self.b)) - self.eval_unit(t);
+
+        let mut res: Vec<(f64, f64)> = vec![];
+        for t in grid(self.degree()) {
+            let e = error(t);
+            match res.last_mut() {
+                Some(last) if (last.1 >= 0.) == (e >= 0.) => {
+                    if e.abs() > last.1.abs() {
+                        *last = (t, e);
+                    }
+                }
+                _ => res.push((t, e)),
+            }
+        }
+
+        if res.len() < n {
+            return None;
+        }
+        while res.len() > n {
+            if res[0].1.abs() < res[res.len() - 1].1.abs() {
+                res.remove(0);
+            } else {
+                res.pop();
+            }
+        }
+        Some(res.into_iter().map(|(t, _)| t).collect())
+    }
+
+    pub fn degree(&self) -> usize {
+        self.chebyshev.len().saturating_sub(1)
+    }
+
+    pub fn interval(&self) -> (f64, f64) {
+        (self.a, self.b)
+    }
+
+    ///
+    /// Largest absolute error, measured on a dense grid of the interval
+    ///
+    pub fn max_error(&self) -> f64 {
+        self.max_error
+    }
+
+    ///
+    /// Coefficients c_k of the approximation sum c_k T_k(t), with t = (2x - a - b) / (b - a)
+    ///
+    pub fn chebyshev_coefficients(&self) -> &[f64] {
+        &self.chebyshev
+    }
+
+    ///
+    /// The approximation as a polynomial in x. Large degrees or intervals far from [-1, 1] make
+    /// the coefficients large and cancelling, so prefer the Chebyshev basis when possible.
+    ///
+    pub fn power_basis(&self) -> Polynomial<f64> {
+        // T_0 = 1, T_1 = t and T_k+1 = 2t T_k - T_k-1
+        let t = Polynomial::new(vec![0., 1.]);
+        let two_t = Polynomial::new(vec![0., 2.]);
+        let (mut prev, mut cur) = (Polynomial::new(vec![1.]), t.clone());
+
+        let mut res = Polynomial::new(vec![]);
+        for (k, c) in self.chebyshev.iter().enumerate() {
+            let basis = match k {
+                0 => prev.clone(),
+                1 => cur.clone(),
+                _ => {
+                    let next = &(&two_t * &cur) - &prev;
+                    prev = std::mem::replace(&mut cur, next);
+                    cur.clone()
+                }
+            };
+            res = &res + &basis.scale(c);
+        }
+
+        // t as a polynomial in x
+        let scale = 2. / (self.b - self.a);
+        let to_unit = Polynomial::new(vec![-(self.a + self.b) / (self.b - self.a), scale]);
+        res.compose(&to_unit)
+    }
+
+    ///
+    /// Evaluate at x with Clenshaw's algorithm
+    ///
+    pub fn eval(&self, x: f64) -> f64 {
+        self.eval_unit((2. * x - self.a - self.b) / (self.b - self.a))
+    }
+
+    fn eval_unit(&self, t: f64) -> f64 {
+        let (mut b1, mut b2) = (0., 0.);
+        for c in self.chebyshev.iter().skip(1).rev() {
+            let b0 = c + 2. * t * b1 - b2;
+            b2 = b1;
+            b1 = b0;
+        }
+        self.chebyshev.first().copied().unwrap_or(0.) + t * b1 - b2
+    }
+}
+
+///
+/// Map t in [-1, 1] to [a, b]
+///
+fn from_unit(t: f64, a: f64, b: f64) -> f64 {
+    (a + b) / 2. + t * (b - a) / 2.
+}
+
+///
+/// T_0(t), ..., T_n-1(t)
+///
+fn chebyshev_basis(t: f64, n: usize) -> Vec<f64> {
+    let mut res = vec![1., t];
+    for k in 2..n {
+        res.push(2. * t * res[k - 1] - res[k - 2]);
+    }
+    res.truncate(n);
+    res
+}
+
+fn grid(degree: usize) -> impl Iterator<Item = f64> {
+    let points = GRID_DENSITY * (degree + 1);
+    (0..=points).map(move |i| -1. + 2. * i as f64 / points as f64)
+}
+
+///
+/// Solve the square system m x = rhs with Gaussian elimination and partial pivoting, or None if
+/// it is singular
+///
+fn solve(mut m: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>> {
+    let n = rhs.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| m[i][col].abs().total_cmp(&m[j][col].abs()))?;
+        if m[pivot][col].abs() < f64::EPSILON {
+            return None;
+        }
+        m.swap(col, pivot);
+        rhs.swap(col, pivot);
+        let (top, bottom) = m.split_at_mut(col + 1);
+        let pivot_row = &top[col];
+        for (row, r) in bottom.iter_mut().enumerate() {
+            let factor = r[col] / pivot_row[col];
+            for (x, p) in r[col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor * p;
+            }
+            rhs[col + 1 + row] -= factor * rhs[col];
+        }
+    }
+
+    let mut x = vec![0.; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| m[row][k] * x[k]).sum();
+        x[row] = (rhs[row] - sum) / m[row][row];
+    }
+    Some(x)
+}
@@ -0,0 +1,323 @@
+use num_bigint::BigInt;
+use num_traits::Zero;
+
+use algebra::modulus::Modulus;
+
+use crate::polynomial_ring::{eval_op, eval_op_assign};
+use crate::PolynomialRing;
+
+///
+/// The coefficients of a `PolynomialRing`: elements of a commutative ring, together with the
+/// context their arithmetic needs, such as the modulus for integers modulo p.
+///
+/// Implemented for
+///  - `BigInt`, the integers, reduced explicitly with `%`. Polynomials can use a Chinese
+///    Remainder Theorem context for fast multiplication and the evaluation form.
+///  - `i64`, the integers, for small parameters that are known not to overflow
+///  - `u64`, the integers modulo the `Modulus` in the context, as values in [0, p)
+///  - `Vec<u64>`, residues modulo every `Modulus` in the context (an RNS representation)
+///
+pub trait Coefficient: Clone + PartialEq + std::fmt::Debug + Send + Sync {
+    type Context: Clone + PartialEq + std::fmt::Debug + Send + Sync;
+
+    fn ring_zero(ctx: &Self::Context) -> Self;
+    fn ring_one(ctx: &Self::Context) -> Self;
+    fn ring_is_zero(&self) -> bool;
+    fn ring_add(&self, other: &Self, ctx: &Self::Context) -> Self;
+    fn ring_sub(&self, other: &Self, ctx: &Self::Context) -> Self;
+    fn ring_neg(&self, ctx: &Self::Context) -> Self;
+    fn ring_mul(&self, other: &Self, ctx: &Self::Context) -> Self;
+
+    fn ring_add_assign(&mut self, other: &Self, ctx: &Self::Context) {
+        *self = self.ring_add(other, ctx);
+    }
+
+    fn ring_sub_assign(&mut self, other: &Self, ctx: &Self::Context) {
+        *self = self.ring_sub(other, ctx);
+    }
+
+    fn ring_neg_assign(&mut self, ctx: &Self::Context) {
+        *self = self.ring_neg(ctx);
+    }
+
+    ///
+    /// a + b in R/[X^N + 1]. Types with faster representations can override this.
+    ///
+    fn poly_add<'a>(
+        a: &PolynomialRing<'a, Self>,
+        b: &PolynomialRing<'a, Self>,
+    ) -> PolynomialRing<'a, Self> {
+        a.add_coefficients(b)
+    }
+
+    ///
+    /// a - b in R/[X^N + 1]
+    ///
+    fn poly_sub<'a>(
+        a: &PolynomialRing<'a, Self>,
+        b: &PolynomialRing<'a, Self>,
+    ) -> PolynomialRing<'a, Self> {
+        a.sub_coefficients(b)
+    }
+
+    ///
+    /// a * b in R/[X^N + 1], the schoolbook way unless overridden
+    ///
+    fn poly_mul<'a>(
+        a: &PolynomialRing<'a, Self>,
+        b: &PolynomialRing<'a, Self>,
+    ) -> PolynomialRing<'a, Self> {
+        a.mul_schoolbook(b)
+    }
+
+    ///
+    /// a += b, in place on the coefficients of a
+    ///
+    fn poly_add_assign<'a>(a: &mut PolynomialRing<'a, Self>, b: &PolynomialRing<'a, Self>) {
+        a.add_assign_coefficients(b)
+    }
+
+    ///
+    /// a -= b, in place on the coefficients of a
+    ///
+    fn poly_sub_assign<'a>(a: &mut PolynomialRing<'a, Self>, b: &PolynomialRing<'a, Self>) {
+        a.sub_assign_coefficients(b)
+    }
+
+    ///
+    /// a *= b. A product needs a new vector in coefficient form, so this is only in place for
+    /// types that override it.
+    ///
+    fn poly_mul_assign<'a>(a: &mut PolynomialRing<'a, Self>, b: &PolynomialRing<'a, Self>) {
+        *a = Self::poly_mul(a, b)
+    }
+}
+
+impl Coefficient for i64 {
+    type Context = ();
+
+    fn ring_zero(_: &()) -> Self {
+        0
+    }
+
+    fn ring_one(_: &()) -> Self {
+        1
+    }
+
+    fn ring_is_zero(&self) -> bool {
+        *self == 0
+    }
+
+    fn ring_add(&self, other: &Self, _: &()) -> Self {
+        self + other
+    }
+
+    fn ring_sub(&self, other: &Self, _: &()) -> Self {
+        self - other
+    }
+
+    fn ring_neg(&self, _: &()) -> Self {
+        -self
+    }
+
+    fn ring_mul(&self, other: &Self, _: &()) -> Self {
+        self * other
+    }
+}
+
+impl Coefficient for u64 {
+    type Context = Modulus;
+
+    fn ring_zero(_: &Modulus) -> Self {
+        0
+    }
+
+    fn ring_one(_: &Modulus) -> Self {
+        1
+    }
+
+    fn ring_is_zero(&self) -> bool {
+        *self == 0
+    }
+
+    fn ring_add(&self, other: &Self, ctx: &Modulus) -> Self {
+        ctx.add_mod(*self, *other)
+    }
+
+    fn ring_sub(&self, other: &Self, ctx: &Modulus) -> Self {
+        ctx.sub_mod(*self, *other)
+    }
+
+    fn ring_neg(&self, ctx: &Modulus) -> Self {
+        ctx.neg_mod(*self)
+    }
+
+    fn ring_mul(&self, other: &Self, ctx: &Modulus) -> Self {
+        ctx.mul_mod(*self, *other)
+    }
+}
+
+impl Coefficient for Vec<u64> {
+    type Context = Vec<Modulus>;
+
+    fn ring_zero(ctx: &Vec<Modulus>) -> Self {
+        vec![0; ctx.len()]
+    }
+
+    fn ring_one(ctx: &Vec<Modulus>) -> Self {
+        vec![1; ctx.len()]
+    }
+
+    fn ring_is_zero(&self) -> bool {
+        self.iter().all(|&x| x == 0)
+    }
+
+    fn ring_add(&self, other: &Self, ctx: &Vec<Modulus>) -> Self {
+        let mut res = self.clone();
+        residue_op(&mut res, other, ctx, |m, x, y| m.add_mod(x, y));
+        res
+    }
+
+    fn ring_sub(&self, other: &Self, ctx: &Vec<Modulus>) -> Self {
+        let mut res = self.clone();
+        residue_op(&mut res, other, ctx, |m, x, y| m.sub_mod(x, y));
+        res
+    }
+
+    fn ring_neg(&self, ctx: &Vec<Modulus>) -> Self {
+        self.iter().zip(ctx).map(|(&x, m)| m.neg_mod(x)).collect()
+    }
+
+    fn ring_mul(&self, other: &Self, ctx: &Vec<Modulus>) -> Self {
+        let mut res = self.clone();
+        residue_op(&mut res, other, ctx, |m, x, y| m.mul_mod(x, y));
+        res
+    }
+
+    fn ring_add_assign(&mut self, other: &Self, ctx: &Vec<Modulus>) {
+        residue_op(self, other, ctx, |m, x, y| m.add_mod(x, y));
+    }
+
+    fn ring_sub_assign(&mut self, other: &Self, ctx: &Vec<Modulus>) {
+        residue_op(self, other, ctx, |m, x, y| m.sub_mod(x, y));
+    }
+
+    fn ring_neg_assign(&mut self, ctx: &Vec<Modulus>) {
+        self.iter_mut().zip(ctx).for_each(|(x, m)| *x = m.neg_mod(*x));
+    }
+}
+
+fn residue_op(a: &mut [u64], b: &[u64], ctx: &[Modulus], op: impl Fn(&Modulus, u64, u64) -> u64) {
+    assert!(
+        a.len() == ctx.len() && b.len() == ctx.len(),
+        "residues do not match the number of moduli"
+    );
+    for ((x, &y), m) in a.iter_mut().zip(b).zip(ctx) {
+        *x = op(m, *x, y);
+    }
+}
+
+impl Coefficient for BigInt {
+    type Context = ();
+
+    fn ring_zero(_: &()) -> Self {
+        BigInt::zero()
+    }
+
+    fn ring_one(_: &()) -> Self {
+        BigInt::from(1)
+    }
+
+    fn ring_is_zero(&self) -> bool {
+        self.is_zero()
+    }
+
+    fn ring_add(&self, other: &Self, _: &()) -> Self {
+        self + other
+    }
+
+    fn ring_sub(&self, other: &Self, _: &()) -> Self {
+        self - other
+    }
+
+    fn ring_neg(&self, _: &()) -> Self {
+        -self
+    }
+
+    fn ring_mul(&self, other: &Self, _: &()) -> Self {
+        self * other
+    }
+
+    fn ring_add_assign(&mut self, other: &Self, _: &()) {
+        *self += other;
+    }
+
+    fn ring_sub_assign(&mut self, other: &Self, _: &()) {
+        *self -= other;
+    }
+
+    fn ring_neg_assign(&mut self, _: &()) {
+        *self = -std::mem::take(self);
+    }
+
+    fn poly_add<'a>(
+        a: &PolynomialRing<'a, Self>,
+        b: &PolynomialRing<'a, Self>,
+    ) -> PolynomialRing<'a, Self> {
+        if a.is_ntt() || b.is_ntt() {
+            return eval_op(a, b, |m, x, y| m.add_vec(x, y));
+        }
+        a.add_coefficients(b)
+    }
+
+    fn poly_sub<'a>(
+        a: &PolynomialRing<'a, Self>,
+        b: &PolynomialRing<'a, Self>,
+    ) -> PolynomialRing<'a, Self> {
+        if a.is_ntt() || b.is_ntt() {
+            return eval_op(a, b, |m, x, y| m.sub_vec(x, y));
+        }
+        a.sub_coefficients(b)
+    }
+
+    ///
+    /// In evaluation form, or with a Chinese Remainder Theorem context, the product is computed
+    /// with the NTT modulo its primes. Otherwise `MulStrategy::Auto` picks the algorithm.
+    ///
+    fn poly_mul<'a>(
+        a: &PolynomialRing<'a, Self>,
+        b: &PolynomialRing<'a, Self>,
+    ) -> PolynomialRing<'a, Self> {
+        if a.is_ntt() || b.is_ntt() {
+            return eval_op(a, b, |m, x, y| m.mul_vec(x, y));
+        }
+        if a.crt().is_some() {
+            return eval_op(a, b, |m, x, y| m.mul_vec(x, y)).from_ntt();
+        }
+        a.mul_with(b, crate::MulStrategy::Auto)
+    }
+
+    fn poly_add_assign<'a>(a: &mut PolynomialRing<'a, Self>, b: &PolynomialRing<'a, Self>) {
+        if a.is_ntt() || b.is_ntt() {
+            return eval_op_assign(a, b, |m, x, y| m.add_vec(x, y));
+        }
+        a.add_assign_coefficients(b)
+    }
+
+    fn poly_sub_assign<'a>(a: &mut PolynomialRing<'a, Self>, b: &PolynomialRing<'a, Self>) {
+        if a.is_ntt() || b.is_ntt() {
+            return eval_op_assign(a, b, |m, x, y| m.sub_vec(x, y));
+        }
+        a.sub_assign_coefficients(b)
+    }
+
+    ///
+    /// In place slot by slot when the result stays in evaluation form
+    ///
+    fn poly_mul_assign<'a>(a: &mut PolynomialRing<'a, Self>, b: &PolynomialRing<'a, Self>) {
+        if a.is_ntt() || b.is_ntt() {
+            return eval_op_assign(a, b, |m, x, y| m.mul_vec(x, y));
+        }
+        *a = Self::poly_mul(a, b)
+    }
+}
@@ -1,5 +1,11 @@
+pub mod approximation;
+pub mod coefficient;
+pub mod multiplication;
 pub mod polynomial;
 pub mod polynomial_ring;
 
+pub use approximation::Approximation;
+pub use coefficient::Coefficient;
+pub use multiplication::MulStrategy;
 pub use polynomial::*;
 pub use polynomial_ring::*;
@@ -0,0 +1,227 @@
+use itertools::{iproduct, EitherOrBoth::*, Itertools};
+
+use num_bigint::BigInt;
+use num_traits::Zero;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use algebra::crt::Crt;
+use algebra::modulus::Modulus;
+use algebra::parallel::*;
+use algebra::prime::ntt_primes;
+
+use crate::Modulo;
+
+// Below this many coefficients, Karatsuba splits stop paying off
+const KARATSUBA_CUTOFF: usize = 16;
+
+// Degrees from which the NTT beats Karatsuba, when Auto picks a strategy
+const NTT_THRESHOLD: usize = 64;
+
+// Bits of the primes generated for the NTT strategy
+const NTT_PRIME_BITS: u32 = 60;
+
+///
+/// How to multiply two polynomials in Z[X]/(X^N + 1) with BigInt coefficients
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MulStrategy {
+    /// O(n^2) product of every pair of coefficients
+    Schoolbook,
+    /// O(n^1.58) divide and conquer
+    Karatsuba,
+    /// O(n log n) negacyclic NTT modulo enough primes to hold the exact product, with a
+    /// Chinese Remainder Theorem basis generated from the bit lengths of the operands. N needs to
+    /// be a power of two.
+    Ntt,
+    /// Schoolbook for small degrees, otherwise the NTT when N is a power of two and Karatsuba
+    /// when it is not
+    Auto,
+}
+
+impl MulStrategy {
+    ///
+    /// Product of a and b modulo X^n + 1. The result has min(len(a) + len(b) - 1, n)
+    /// coefficients, as if computed with the schoolbook product and then reduced.
+    ///
+    pub fn mul(self, a: &[BigInt], b: &[BigInt], n: usize) -> Vec<BigInt> {
+        if a.is_empty() || b.is_empty() {
+            return vec![];
+        }
+        match self {
+            MulStrategy::Schoolbook => fold(schoolbook(a, b), n),
+            MulStrategy::Karatsuba => fold(karatsuba(a, b), n),
+            MulStrategy::Ntt => ntt(a, b, n),
+            MulStrategy::Auto => {
+                let strategy = if n < NTT_THRESHOLD {
+                    MulStrategy::Schoolbook
+                } else if n.is_power_of_two() {
+                    MulStrategy::Ntt
+                } else {
+                    MulStrategy::Karatsuba
+                };
+                strategy.mul(a, b, n)
+            }
+        }
+    }
+}
+
+///
+/// Full product of a and b, without reducing modulo X^n + 1
+///
+fn schoolbook(a: &[BigInt], b: &[BigInt]) -> Vec<BigInt> {
+    let mut res = vec![BigInt::zero(); a.len() + b.len() - 1];
+    for ((i1, v1), (i2, v2)) in iproduct!(a.iter().enumerate(), b.iter().enumerate()) {
+        res[i1 + i2] += v1 * v2;
+    }
+    res
+}
+
+///
+/// Full product of a and b with Karatsuba's method: with a = a0 + a1 * X^m and
+/// b = b0 + b1 * X^m, a * b = z0 + z1 * X^m + z2 * X^2m where z0 = a0 * b0, z2 = a1 * b1 and
+/// z1 = (a0 + a1) * (b0 + b1) - z0 - z2 takes a single product instead of two.
+///
+fn karatsuba(a: &[BigInt], b: &[BigInt]) -> Vec<BigInt> {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    if a.len().min(b.len()) <= KARATSUBA_CUTOFF {
+        return schoolbook(a, b);
+    }
+
+    let m = a.len().max(b.len()) / 2;
+    let (a0, a1) = a.split_at(m.min(a.len()));
+    let (b0, b1) = b.split_at(m.min(b.len()));
+
+    let (z0, z2) = join(|| karatsuba(a0, b0), || karatsuba(a1, b1));
+    let z1 = karatsuba(&add(a0, a1), &add(b0, b1));
+
+    // The sums can be longer than the halves, so leave room for the zero terms of z1
+    let mut res = vec![BigInt::zero(); 2 * (a.len() + b.len())];
+    for (i, z) in z0.iter().enumerate() {
+        res[i] += z;
+        res[i + m] -= z;
+    }
+    for (i, z) in z2.iter().enumerate() {
+        res[i + 2 * m] += z;
+        res[i + m] -= z;
+    }
+    for (i, z) in z1.iter().enumerate() {
+        res[i + m] += z;
+    }
+    res.truncate(a.len() + b.len() - 1);
+    res
+}
+
+fn add(a: &[BigInt], b: &[BigInt]) -> Vec<BigInt> {
+    a.iter()
+        .zip_longest(b)
+        .map(|x| match x {
+            Both(a, b) => a + b,
+            Left(a) => a.clone(),
+            Right(a) => a.clone(),
+        })
+        .collect()
+}
+
+///
+/// Reduce a polynomial modulo X^n + 1, keeping at most n coefficients
+///
+fn fold(mut coef: Vec<BigInt>, n: usize) -> Vec<BigInt> {
+    if coef.len() <= n {
+        return coef;
+    }
+    for i in n..coef.len() {
+        let c = std::mem::take(&mut coef[i]);
+        // X^n = -1
+        if (i / n) & 1 == 1 {
+            coef[i % n] -= c;
+        } else {
+            coef[i % n] += c;
+        }
+    }
+    coef.truncate(n);
+    coef
+}
+
+///
+/// Reduce coefficients into [0, p) as a polynomial modulo X^n + 1 of exactly n coefficients
+///
+pub(crate) fn residues(coef: &[BigInt], modulus: &Modulus, n: usize) -> Vec<u64> {
+    let mut res = vec![0u64; n];
+    for (i, x) in coef.iter().enumerate() {
+        let r = modulus.reduce_bigint(x);
+        // X^n = -1
+        if (i / n) & 1 == 1 {
+            res[i % n] = modulus.sub_mod(res[i % n], r);
+        } else {
+            res[i % n] = modulus.add_mod(res[i % n], r);
+        }
+    }
+    res
+}
+
+///
+/// The Chinese Remainder Theorem basis of `num_primes` NTT primes for degree n, with its twiddle
+/// tables. Finding the primes and the tables costs more than a small product, so every basis is
+/// only built once and then shared.
+///
+fn ntt_basis(n: usize, num_primes: usize) -> Arc<Crt> {
+    // Keyed by (n, num_primes)
+    type Bases = Mutex<HashMap<(usize, usize), Arc<Crt>>>;
+    static BASES: OnceLock<Bases> = OnceLock::new();
+
+    let mut bases = BASES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    bases
+        .entry((n, num_primes))
+        .or_insert_with(|| {
+            let primes = ntt_primes(&vec![NTT_PRIME_BITS; num_primes], n as u64);
+            Arc::new(Crt::from_primes(&primes, n as u64))
+        })
+        .clone()
+}
+
+///
+/// Product of a and b modulo X^n + 1 with the NTT, modulo enough primes that the coefficients of
+/// the product can be recovered exactly
+///
+fn ntt(a: &[BigInt], b: &[BigInt], n: usize) -> Vec<BigInt> {
+    assert!(
+        n.is_power_of_two(),
+        "NTT multiplication needs the polynomial degree to be a power of 2"
+    );
+
+    // |c_k| <= len(a) * len(b) * max|a_i| * max|b_j|, and one more bit for the sign
+    let max_bits = |x: &[BigInt]| x.iter().map(|c| c.bits()).max().unwrap_or(0);
+    let terms = (usize::BITS - (a.len() * b.len()).leading_zeros()) as u64;
+    let bits = max_bits(a) + max_bits(b) + terms + 1;
+
+    // The primes can lie just below 2^NTT_PRIME_BITS
+    let num_primes = bits.div_ceil(NTT_PRIME_BITS as u64 - 1) as usize;
+    let crt = ntt_basis(n, num_primes);
+
+    let prod: Vec<Vec<u64>> = crt
+        .ntts
+        .par_iter()
+        .map(|ntt| {
+            let mut x = residues(a, ntt.modulus(), n);
+            let mut y = residues(b, ntt.modulus(), n);
+            ntt.forward(&mut x);
+            ntt.forward(&mut y);
+            ntt.pointwise_mul(&mut x, &y);
+            ntt.inverse(&mut x);
+            x
+        })
+        .collect();
+
+    let mut coef = crt.reconstruct_poly(&prod);
+    coef.truncate(a.len() + b.len() - 1);
+    coef.into_par_iter()
+        .map(|x| x.mod_ring(&crt.modulus))
+        .collect()
+}
@@ -1,6 +1,130 @@
-use itertools::{iproduct, EitherOrBoth::*, Itertools};
+use itertools::{EitherOrBoth::*, Itertools};
 
-#[derive(PartialEq, Debug)]
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_rational::Ratio;
+use num_traits::{Num, Zero};
+use std::convert::TryFrom;
+
+use algebra::utils::reverse_bits;
+
+use crate::multiplication::MulStrategy;
+
+// Number of coefficients of a product of floats from which the FFT is used
+const FFT_THRESHOLD: usize = 64;
+// Number of coefficients of a product of machine integers from which it goes through BigInt
+const BIGINT_THRESHOLD: usize = 64;
+
+///
+/// Coefficients of a `Polynomial`: integers, rationals or floats.
+///
+/// Division only needs to be exact for the integers, so Euclidean division, GCD and
+/// interpolation are meant for rationals and floats, or integer polynomials whose divisors have
+/// a leading coefficient that divides evenly (e.g. monic ones).
+///
+pub trait Scalar: Num + Clone + std::ops::Neg<Output = Self> + std::fmt::Debug {
+    ///
+    /// Whether b divides a, which always holds in a field
+    ///
+    fn divides(a: &Self, b: &Self) -> bool {
+        let _ = a;
+        !b.is_zero()
+    }
+
+    ///
+    /// Whether the values are all zero. For floats, up to rounding relative to the largest
+    /// absolute value in `reference`.
+    ///
+    fn all_negligible(values: &[Self], reference: &[Self]) -> bool {
+        let _ = reference;
+        values.iter().all(|x| x.is_zero())
+    }
+
+    ///
+    /// Full product of two non-empty coefficient vectors, if there is a faster way than the
+    /// schoolbook product
+    ///
+    fn fast_mul(a: &[Self], b: &[Self]) -> Option<Vec<Self>> {
+        let _ = (a, b);
+        None
+    }
+}
+
+macro_rules! impl_scalar_int {
+    ($($t:ty),*) => {$(
+        impl Scalar for $t {
+            fn divides(a: &Self, b: &Self) -> bool {
+                *b != 0 && a % b == 0
+            }
+
+            ///
+            /// Exact product with the NTT, through BigInt, for products long enough to pay for
+            /// the conversions
+            ///
+            fn fast_mul(a: &[Self], b: &[Self]) -> Option<Vec<Self>> {
+                if a.len() + b.len() - 1 < BIGINT_THRESHOLD {
+                    return None;
+                }
+                let big = |x: &[Self]| x.iter().map(|&c| BigInt::from(c)).collect::<Vec<_>>();
+                let prod = BigInt::fast_mul(&big(a), &big(b))?;
+                Some(
+                    prod.iter()
+                        .map(|c| {
+                            <$t>::try_from(c)
+                                .expect("polynomial product overflows the coefficient type")
+                        })
+                        .collect(),
+                )
+            }
+        }
+    )*};
+}
+
+impl_scalar_int!(i32, i64);
+
+impl Scalar for BigInt {
+    fn divides(a: &Self, b: &Self) -> bool {
+        !b.is_zero() && (a % b).is_zero()
+    }
+
+    fn fast_mul(a: &[Self], b: &[Self]) -> Option<Vec<Self>> {
+        // Pad to a power of two that is large enough for X^n + 1 to never wrap around
+        let n = (a.len() + b.len() - 1).next_power_of_two();
+        Some(MulStrategy::Auto.mul(a, b, n))
+    }
+}
+
+macro_rules! impl_scalar_float {
+    ($($t:ty),*) => {$(
+        impl Scalar for $t {
+            fn all_negligible(values: &[Self], reference: &[Self]) -> bool {
+                let scale = reference.iter().fold(0 as $t, |acc, x| acc.max(x.abs()));
+                values.iter().all(|x| x.abs() <= 1e-9 * scale)
+            }
+
+            fn fast_mul(a: &[Self], b: &[Self]) -> Option<Vec<Self>> {
+                if a.len() + b.len() - 1 < FFT_THRESHOLD {
+                    return None;
+                }
+                let to_f64 = |x: &[Self]| x.iter().map(|&c| c as f64).collect::<Vec<_>>();
+                Some(
+                    fft_mul(&to_f64(a), &to_f64(b))
+                        .into_iter()
+                        .map(|c| c as $t)
+                        .collect(),
+                )
+            }
+        }
+    )*};
+}
+
+impl_scalar_float!(f32, f64);
+
+impl Scalar for Ratio<i64> {}
+
+impl Scalar for Ratio<BigInt> {}
+
+#[derive(PartialEq, Debug, Clone)]
 pub struct Polynomial<T> {
     coef: Vec<T>,
 }
@@ -12,11 +136,18 @@ macro_rules! polynomial {
     }
 }
 
-impl Polynomial<i32> {
-    pub fn new(coef: Vec<i32>) -> Self {
+impl<T: Scalar> Polynomial<T> {
+    ///
+    /// Create a polynomial from its coefficients, starting with the constant term
+    ///
+    pub fn new(coef: Vec<T>) -> Self {
         Self { coef }
     }
 
+    pub fn coef(&self) -> &[T] {
+        &self.coef
+    }
+
     pub fn len(&self) -> usize {
         self.coef.len()
     }
@@ -25,57 +156,331 @@ impl Polynomial<i32> {
         self.len() == 0
     }
 
+    ///
+    /// Whether all the coefficients are zero
+    ///
+    pub fn is_zero(&self) -> bool {
+        self.coef.iter().all(|c| c.is_zero())
+    }
+
+    ///
+    /// Degree, ignoring trailing zeros. The zero polynomial has degree 0.
+    ///
+    pub fn degree(&self) -> usize {
+        self.coef
+            .iter()
+            .rposition(|c| !c.is_zero())
+            .unwrap_or_default()
+    }
+
+    ///
+    /// Remove trailing zeros of polynomial
+    ///
+    pub fn clean(mut self) -> Self {
+        let len = self
+            .coef
+            .iter()
+            .rposition(|c| !c.is_zero())
+            .map_or(0, |i| i + 1);
+        self.coef.truncate(len);
+        self
+    }
+
     // Take the function modulo of self with (X^n + 1)
     pub fn mod_cyc(&mut self, n: usize) {
         if self.len() >= n {
             let diff = self.len() - n;
             for i in n..n + diff {
-                self.coef[i - n] -= self.coef[i];
-                self.coef[i] = 0;
+                self.coef[i - n] = self.coef[i - n].clone() - self.coef[i].clone();
+                self.coef[i] = T::zero();
             }
             self.coef.drain(n..);
         }
     }
 
+    ///
+    /// Multiply every coefficient by c
+    ///
+    pub fn scale(&self, c: &T) -> Self {
+        Polynomial::new(self.coef.iter().map(|x| x.clone() * c.clone()).collect())
+    }
+
+    ///
+    /// Evaluate at x with Horner's rule
+    ///
+    pub fn eval(&self, x: &T) -> T {
+        self.coef
+            .iter()
+            .rev()
+            .fold(T::zero(), |acc, c| acc * x.clone() + c.clone())
+    }
+
+    ///
+    /// Formal derivative
+    ///
+    pub fn derivative(&self) -> Self {
+        let mut k = T::zero();
+        let coef = self
+            .coef
+            .iter()
+            .skip(1)
+            .map(|c| {
+                k = k.clone() + T::one();
+                c.clone() * k.clone()
+            })
+            .collect();
+        Polynomial::new(coef).clean()
+    }
+
+    ///
+    /// Composition p(q(X)), with Horner's rule on polynomials
+    ///
+    pub fn compose(&self, other: &Self) -> Self {
+        self.coef
+            .iter()
+            .rev()
+            .fold(Polynomial::new(vec![]), |acc, c| {
+                &(&acc * other) + &Polynomial::new(vec![c.clone()])
+            })
+            .clean()
+    }
+
+    ///
+    /// Euclidean division: the quotient q and remainder r with self = q * divisor + r and
+    /// deg(r) < deg(divisor)
+    ///
+    /// Panics if the divisor is zero, or if a leading coefficient does not divide evenly over
+    /// the integers.
+    ///
+    pub fn div_rem(&self, divisor: &Self) -> (Self, Self) {
+        let divisor = divisor.clone().clean();
+        assert!(!divisor.is_empty(), "div_rem: division by zero polynomial");
+
+        let mut rem = self.clone().clean();
+        let d = divisor.len() - 1;
+        let lead = &divisor.coef[d];
+        if rem.len() <= d {
+            return (Polynomial::new(vec![]), rem);
+        }
+
+        let mut quot = vec![T::zero(); rem.len() - d];
+        for i in (0..quot.len()).rev() {
+            let c = rem.coef[i + d].clone();
+            assert!(
+                T::divides(&c, lead) || c.is_zero(),
+                "div_rem: leading coefficient of the divisor does not divide evenly"
+            );
+            let q = c / lead.clone();
+            for (j, x) in divisor.coef.iter().enumerate().take(d) {
+                rem.coef[i + j] = rem.coef[i + j].clone() - q.clone() * x.clone();
+            }
+            // The leading term cancels by construction, which floats may only do approximately
+            rem.coef[i + d] = T::zero();
+            quot[i] = q;
+        }
+        (Polynomial::new(quot).clean(), rem.clean())
+    }
+
+    ///
+    /// Greatest common divisor with the Euclidean algorithm, made monic. The coefficients need to
+    /// form a field; for floats, a remainder counts as zero once its coefficients are negligible
+    /// next to the largest coefficient of the operands.
+    ///
+    pub fn gcd(&self, other: &Self) -> Self {
+        // Remainders are compared to the operands, so that rounding errors count as zero for floats
+        let reference: Vec<T> = self.coef.iter().chain(&other.coef).cloned().collect();
+        let mut a = self.clone().clean();
+        let mut b = other.clone().clean();
+        while !T::all_negligible(&b.coef, &reference) {
+            let (_, r) = a.div_rem(&b);
+            a = b;
+            b = r;
+        }
+        match a.coef.last() {
+            Some(lead) => {
+                let lead = lead.clone();
+                Polynomial::new(a.coef.into_iter().map(|c| c / lead.clone()).collect())
+            }
+            None => a,
+        }
+    }
+
+    ///
+    /// Lagrange interpolation: the polynomial of degree less than n through the n points
+    /// (xs[i], ys[i]). The xs need to be distinct.
+    ///
+    pub fn interpolate(xs: &[T], ys: &[T]) -> Self {
+        assert_eq!(xs.len(), ys.len(), "interpolate: need as many xs as ys");
+
+        // M(X) = prod (X - x_j), so that the Lagrange basis polynomial l_i is
+        // M(X) / ((X - x_i) * M'(x_i))
+        let master = xs.iter().fold(polynomial![T::one()], |acc, x| {
+            &acc * &polynomial![-x.clone(), T::one()]
+        });
+
+        let mut res = Polynomial::new(vec![]);
+        for (x, y) in xs.iter().zip(ys) {
+            let (basis, _) = master.div_rem(&polynomial![-x.clone(), T::one()]);
+            let denom = basis.eval(x);
+            assert!(!denom.is_zero(), "interpolate: xs need to be distinct");
+            res = &res + &basis.scale(&(y.clone() / denom));
+        }
+        res.clean()
+    }
+}
+
+impl Polynomial<i32> {
     pub fn rem_euclid(&mut self, q: i32) {
         self.coef = self.coef.iter().map(|x| x.rem_euclid(q)).collect()
     }
 }
 
-impl std::ops::Add for Polynomial<i32> {
-    type Output = Self;
-    fn add(self, other: Polynomial<i32>) -> Self {
+impl<T: Scalar> std::ops::Add<&Polynomial<T>> for &Polynomial<T> {
+    type Output = Polynomial<T>;
+    fn add(self, other: &Polynomial<T>) -> Polynomial<T> {
         let out = other
             .coef
             .iter()
             .zip_longest(self.coef.iter())
             .map(|x| match x {
-                Both(a, b) => a + b,
-                Left(a) => *a,
-                Right(a) => *a,
+                Both(a, b) => a.clone() + b.clone(),
+                Left(a) => a.clone(),
+                Right(a) => a.clone(),
             })
             .collect();
         Polynomial::new(out)
     }
 }
 
-// TODO: Use FFT to do this in O(nlogn) instead of O(n^2)
-//
-// See; https://math.stackexchange.com/questions/764727/concrete-fft-polynomial-multiplication-example
-impl std::ops::Mul for Polynomial<i32> {
+impl<T: Scalar> std::ops::Add for Polynomial<T> {
     type Output = Self;
-    fn mul(self, other: Polynomial<i32>) -> Self {
-        let mut res = vec![0; other.len() + self.len() - 1];
-        for ((i1, v1), (i2, v2)) in
-            iproduct!(other.coef.iter().enumerate(), self.coef.iter().enumerate())
-        {
-            res[i1 + i2] += v1 * v2;
+    fn add(self, other: Polynomial<T>) -> Self {
+        &self + &other
+    }
+}
+
+impl<T: Scalar> std::ops::Sub<&Polynomial<T>> for &Polynomial<T> {
+    type Output = Polynomial<T>;
+    fn sub(self, other: &Polynomial<T>) -> Polynomial<T> {
+        let out = self
+            .coef
+            .iter()
+            .zip_longest(other.coef.iter())
+            .map(|x| match x {
+                Both(a, b) => a.clone() - b.clone(),
+                Left(a) => a.clone(),
+                Right(b) => -b.clone(),
+            })
+            .collect();
+        Polynomial::new(out)
+    }
+}
+
+impl<T: Scalar> std::ops::Sub for Polynomial<T> {
+    type Output = Self;
+    fn sub(self, other: Polynomial<T>) -> Self {
+        &self - &other
+    }
+}
+
+impl<T: Scalar> std::ops::Neg for &Polynomial<T> {
+    type Output = Polynomial<T>;
+    fn neg(self) -> Polynomial<T> {
+        Polynomial::new(self.coef.iter().map(|c| -c.clone()).collect())
+    }
+}
+
+impl<T: Scalar> std::ops::Neg for Polynomial<T> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        -&self
+    }
+}
+
+///
+/// Product in O(n log n) with the NTT for integers and the FFT for large float polynomials,
+/// and the schoolbook product otherwise
+///
+impl<T: Scalar> std::ops::Mul<&Polynomial<T>> for &Polynomial<T> {
+    type Output = Polynomial<T>;
+    fn mul(self, other: &Polynomial<T>) -> Polynomial<T> {
+        if self.is_empty() || other.is_empty() {
+            return Polynomial::new(vec![]);
+        }
+        if let Some(res) = T::fast_mul(&self.coef, &other.coef) {
+            return Polynomial::new(res);
+        }
+        let mut res = vec![T::zero(); other.len() + self.len() - 1];
+        for (i1, v1) in other.coef.iter().enumerate() {
+            for (i2, v2) in self.coef.iter().enumerate() {
+                res[i1 + i2] = res[i1 + i2].clone() + v1.clone() * v2.clone();
+            }
         }
         Polynomial::new(res)
     }
 }
 
-impl std::fmt::Display for Polynomial<i32> {
+impl<T: Scalar> std::ops::Mul for Polynomial<T> {
+    type Output = Self;
+    fn mul(self, other: Polynomial<T>) -> Self {
+        &self * &other
+    }
+}
+
+///
+/// Full product of two real polynomials with a complex FFT of a power of two length
+///
+fn fft_mul(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let len = a.len() + b.len() - 1;
+    let n = len.next_power_of_two();
+
+    let complex = |x: &[f64]| {
+        let mut v: Vec<Complex64> = x.iter().map(|&c| Complex64::new(c, 0.)).collect();
+        v.resize(n, Complex64::zero());
+        v
+    };
+    let mut x = complex(a);
+    let mut y = complex(b);
+    fft(&mut x, false);
+    fft(&mut y, false);
+    x.iter_mut().zip(&y).for_each(|(x, y)| *x *= y);
+    fft(&mut x, true);
+
+    x.iter().take(len).map(|c| c.re / n as f64).collect()
+}
+
+///
+/// In place iterative radix-2 FFT, without the 1/n factor of the inverse
+///
+fn fft(x: &mut [Complex64], inverse: bool) {
+    let n = x.len();
+    let log_n = n.trailing_zeros();
+    for i in 0..n {
+        let j = reverse_bits(i, log_n);
+        if i < j {
+            x.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1. } else { -1. };
+    let mut m = 2;
+    while m <= n {
+        let w_m = Complex64::from_polar(1., sign * 2. * std::f64::consts::PI / m as f64);
+        for k in (0..n).step_by(m) {
+            let mut w = Complex64::new(1., 0.);
+            for j in 0..m / 2 {
+                let t = w * x[k + j + m / 2];
+                let u = x[k + j];
+                x[k + j] = u + t;
+                x[k + j + m / 2] = u - t;
+                w *= w_m;
+            }
+        }
+        m <<= 1;
+    }
+}
+
+impl<T: Scalar + std::fmt::Display> std::fmt::Display for Polynomial<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let coef = &self.coef;
         if coef.is_empty() {
@@ -83,7 +488,7 @@ impl std::fmt::Display for Polynomial<i32> {
         }
         let mut is_first = true;
         for (i, c) in coef.iter().enumerate().rev() {
-            if *c == 0 {
+            if c.is_zero() {
                 continue;
             }
             if is_first {
@@ -91,7 +496,7 @@ impl std::fmt::Display for Polynomial<i32> {
             } else {
                 write!(f, "+")?
             }
-            if *c == 1 {
+            if c.is_one() {
                 match i {
                     0 => write!(f, "1")?,
                     1 => write!(f, "x")?,
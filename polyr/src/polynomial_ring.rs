@@ -1,14 +1,17 @@
-use itertools::{iproduct, EitherOrBoth::*, Itertools};
 use rand::distributions::{Distribution, Uniform};
 use rand_distr::Normal;
 
 use num_bigint::{BigInt, RandBigInt, ToBigInt};
-use num_traits::{One, ToPrimitive, Zero};
+use num_traits::{One, Signed, ToPrimitive, Zero};
 
 use algebra::crt::Crt;
-use algebra::ntt::Ntt;
+use algebra::modulus::Modulus;
+use algebra::utils::reverse_bits;
 
-use rayon::prelude::*;
+use algebra::parallel::*;
+
+use crate::coefficient::Coefficient;
+use crate::multiplication::{residues, MulStrategy};
 
 ///
 /// Takes a number and maps it into the space (q/2, q/2] for some number q.
@@ -29,62 +32,177 @@ impl Modulo for BigInt {
     }
 }
 
+///
+/// How the values of a polynomial are stored
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Representation {
+    /// The coefficients, in `coef`
+    Coefficient,
+    /// The NTT of the coefficients modulo every prime of the Chinese Remainder Theorem context,
+    /// i.e. the evaluations at the roots of X^N + 1. Multiplication is slot by slot in this form.
+    Evaluation,
+}
+
 #[derive(Debug, Clone)]
-pub struct PolynomialRing<'n, T> {
+pub struct PolynomialRing<'n, T: Coefficient> {
+    /// Coefficients, empty while the polynomial is in evaluation form
     pub coef: Vec<T>,
     pub poly_degree: usize,
     crt: Option<&'n Crt>,
+    representation: Representation,
+    // One vector of NTT values per prime of the crt, while in evaluation form
+    evals: Vec<Vec<u64>>,
+    // What the arithmetic of the coefficients needs, e.g. their modulus
+    context: T::Context,
 }
 
-impl<T> PartialEq for PolynomialRing<'_, T>
-where
-    Vec<T>: PartialEq,
-{
+impl<T: Coefficient> PartialEq for PolynomialRing<'_, T> {
     fn eq(&self, other: &Self) -> bool {
-        self.coef == other.coef && self.poly_degree == other.poly_degree
+        self.coef == other.coef
+            && self.poly_degree == other.poly_degree
+            && self.representation == other.representation
+            && self.evals == other.evals
+            && self.context == other.context
     }
 }
 
-impl<'a> PolynomialRing<'a, BigInt> {
-    ///
-    /// Create a new polynomial in R/[x^N]
+impl<'a, T: Coefficient> PolynomialRing<'a, T> {
     ///
-    /// Where poly_degree is N, and coef are the coefficients
+    /// Create a new polynomial in R/[x^N] whose coefficients need a context, e.g. u64
+    /// coefficients modulo a `Modulus`
     ///
-    pub fn new(poly_degree: usize, coef: Vec<BigInt>) -> Self {
-        // Take the mod to make sure elements are in the ring
+    pub fn with_context(poly_degree: usize, coef: Vec<T>, context: T::Context) -> Self {
         Self {
             coef,
             poly_degree,
             crt: None,
+            representation: Representation::Coefficient,
+            evals: vec![],
+            context,
         }
     }
 
+    pub fn context(&self) -> &T::Context {
+        &self.context
+    }
+
     ///
-    /// Create a new polynomial but with a context to the Chinese Remainder Theorem
+    /// The Chinese Remainder Theorem context, if any
     ///
-    pub fn new_with_crt(poly_degree: usize, coef: Vec<BigInt>, crt: &'a Crt) -> Self {
+    pub fn crt(&self) -> Option<&'a Crt> {
+        self.crt
+    }
+
+    pub fn representation(&self) -> Representation {
+        self.representation
+    }
+
+    pub fn is_ntt(&self) -> bool {
+        self.representation == Representation::Evaluation
+    }
+
+    ///
+    /// A polynomial in evaluation form with the given NTT values, one vector per prime of `crt`
+    ///
+    fn with_evals(&self, crt: &'a Crt, evals: Vec<Vec<u64>>) -> Self {
         Self {
-            coef,
-            poly_degree,
+            coef: vec![],
+            poly_degree: self.poly_degree,
             crt: Some(crt),
+            representation: Representation::Evaluation,
+            evals,
+            context: self.context.clone(),
         }
     }
 
     ///
-    /// Add a Chinese Remainder Theorem context
+    /// A polynomial in coefficient form with the same degree, context and Chinese Remainder
+    /// Theorem context as self
     ///
-    pub fn add_crt(mut self, crt: &'a Crt) -> Self {
-        self.crt = Some(crt);
-        self
+    fn with_coef(&self, coef: Vec<T>) -> Self {
+        Self {
+            coef,
+            poly_degree: self.poly_degree,
+            crt: self.crt,
+            representation: Representation::Coefficient,
+            evals: vec![],
+            context: self.context.clone(),
+        }
     }
 
     ///
-    /// Add a Chinese Remainder Theorem context as an Option
+    /// Compute p(X^k) in R/[X^N + 1] for an odd k, i.e. apply the Galois automorphism X -> X^k
     ///
-    pub fn add_option_crt(mut self, crt: Option<&'a Crt>) -> Self {
-        self.crt = crt;
-        self
+    /// In evaluation form, p(X^k) evaluated at a root w is p evaluated at w^k, which is another
+    /// root, so the values only need to be permuted.
+    ///
+    pub fn automorphism(&self, k: usize) -> Self {
+        let n = self.poly_degree;
+        assert_eq!(k & 1, 1, "automorphism: k needs to be odd");
+        let k = k % (2 * n);
+
+        if self.is_ntt() {
+            // The value at index i is the evaluation at psi^(2 * bitrev(i) + 1)
+            let log_n = n.trailing_zeros();
+            let perm: Vec<usize> = (0..n)
+                .map(|i| {
+                    let exp = ((2 * reverse_bits(i, log_n) + 1) * k) % (2 * n);
+                    reverse_bits((exp - 1) / 2, log_n)
+                })
+                .collect();
+            let evals = self
+                .evals
+                .iter()
+                .map(|x| perm.iter().map(|&j| x[j]).collect())
+                .collect();
+            return self.with_evals(self.crt.unwrap(), evals);
+        }
+
+        let mut coef = vec![T::ring_zero(&self.context); n];
+        for (i, c) in self.coef.iter().enumerate() {
+            let idx = (i * k) % (2 * n);
+            // X^N = -1
+            if idx < n {
+                coef[idx] = coef[idx].ring_add(c, &self.context);
+            } else {
+                coef[idx - n] = coef[idx - n].ring_sub(c, &self.context);
+            }
+        }
+        self.with_coef(coef)
+    }
+
+    ///
+    /// Multiply by the monomial X^j, for j taken modulo 2N. Since X^N = -1, -X^j is X^(j + N).
+    ///
+    pub fn mul_monomial(&self, j: usize) -> Self {
+        let n = self.poly_degree;
+        let j = j % (2 * n);
+
+        if self.is_ntt() {
+            let crt = self.crt.unwrap();
+            let mut monomial = vec![BigInt::zero(); j + 1];
+            monomial[j] = BigInt::one();
+            let monomial = PolynomialRing::new(n, monomial).forward(crt);
+            let mut evals = self.evals.clone();
+            evals
+                .iter_mut()
+                .zip(&monomial)
+                .zip(crt.moduli())
+                .for_each(|((x, y), m)| m.mul_vec(x, y));
+            return self.with_evals(crt, evals);
+        }
+
+        let mut coef = vec![T::ring_zero(&self.context); n];
+        for (i, c) in self.coef.iter().enumerate() {
+            let idx = (i + j) % (2 * n);
+            if idx < n {
+                coef[idx] = coef[idx].ring_add(c, &self.context);
+            } else {
+                coef[idx - n] = coef[idx - n].ring_sub(c, &self.context);
+            }
+        }
+        self.with_coef(coef)
     }
 
     pub fn len(&self) -> usize {
@@ -99,17 +217,12 @@ impl<'a> PolynomialRing<'a, BigInt> {
     /// Remove trailing zeros of polynomial
     ///
     pub fn clean(mut self) -> Self {
-        let mut count = 0;
-        {
-            let coef = self.coef.iter().rev();
-            for c in coef {
-                if *c == Zero::zero() {
-                    count += 1;
-                } else {
-                    break;
-                }
-            }
-        }
+        let count = self
+            .coef
+            .iter()
+            .rev()
+            .take_while(|c| c.ring_is_zero())
+            .count();
         self.coef.drain(self.coef.len() - count..);
         self
     }
@@ -117,19 +230,258 @@ impl<'a> PolynomialRing<'a, BigInt> {
     ///
     /// Take the function modulo of self with (X^n + 1)
     ///
-    fn mod_cyc(mut self) -> Self {
+    fn mod_cyc(&mut self) {
         let n = self.poly_degree;
-        if self.len() >= n {
-            let diff = self.len() - n;
-            for i in n..n + diff {
-                self.coef[i - n] = self.coef[i - n].clone() - self.coef[i].clone();
-                self.coef[i] = Zero::zero();
+        if self.len() > n {
+            let (low, high) = self.coef.split_at_mut(n);
+            for (i, c) in high.iter().enumerate() {
+                low[i].ring_sub_assign(c, &self.context);
             }
-            self.coef.drain(n..);
+            self.coef.truncate(n);
+        }
+    }
+
+    ///
+    /// Add the coefficients of other to those of self, in place
+    ///
+    pub(crate) fn add_assign_coefficients(&mut self, other: &Self) {
+        if self.len() < other.len() {
+            self.coef.resize(other.len(), T::ring_zero(&self.context));
+        }
+        for (x, y) in self.coef.iter_mut().zip(&other.coef) {
+            x.ring_add_assign(y, &self.context);
+        }
+        self.mod_cyc();
+    }
+
+    ///
+    /// Subtract the coefficients of other from those of self, in place
+    ///
+    pub(crate) fn sub_assign_coefficients(&mut self, other: &Self) {
+        if self.len() < other.len() {
+            self.coef.resize(other.len(), T::ring_zero(&self.context));
+        }
+        for (x, y) in self.coef.iter_mut().zip(&other.coef) {
+            x.ring_sub_assign(y, &self.context);
+        }
+        self.mod_cyc();
+    }
+
+    ///
+    /// Sum of the coefficients of self and other, in coefficient form
+    ///
+    pub(crate) fn add_coefficients(&self, other: &Self) -> Self {
+        let mut res = self.clone();
+        res.add_assign_coefficients(other);
+        res
+    }
+
+    ///
+    /// Difference of the coefficients of self and other, in coefficient form
+    ///
+    pub(crate) fn sub_coefficients(&self, other: &Self) -> Self {
+        let mut res = self.clone();
+        res.sub_assign_coefficients(other);
+        res
+    }
+
+    ///
+    /// Negate every coefficient, or every NTT value in evaluation form, in place
+    ///
+    pub fn neg_assign(&mut self) {
+        if let Some(crt) = self.crt.filter(|_| self.is_ntt()) {
+            self.evals
+                .iter_mut()
+                .zip(crt.moduli())
+                .for_each(|(x, m)| m.neg_vec(x));
+            return;
+        }
+        let ctx = &self.context;
+        self.coef.iter_mut().for_each(|c| c.ring_neg_assign(ctx));
+    }
+
+    ///
+    /// Product of self and other modulo X^N + 1, with the product of every pair of coefficients
+    ///
+    pub(crate) fn mul_schoolbook(&self, other: &Self) -> Self {
+        let n = self.poly_degree;
+        let ctx = &self.context;
+        let len = if self.is_empty() || other.is_empty() {
+            0
+        } else {
+            n.min(self.len() + other.len() - 1)
+        };
+        let mut coef = vec![T::ring_zero(ctx); len];
+        for (i, a) in self.coef.iter().enumerate() {
+            for (j, b) in other.coef.iter().enumerate() {
+                let prod = a.ring_mul(b, ctx);
+                // X^N = -1
+                let k = (i + j) % (2 * n);
+                if k < n {
+                    coef[k] = coef[k].ring_add(&prod, ctx);
+                } else {
+                    coef[k - n] = coef[k - n].ring_sub(&prod, ctx);
+                }
+            }
+        }
+        self.with_coef(coef)
+    }
+}
+
+impl<'a> PolynomialRing<'a, BigInt> {
+    ///
+    /// Create a new polynomial in R/[x^N]
+    ///
+    /// Where poly_degree is N, and coef are the coefficients
+    ///
+    pub fn new(poly_degree: usize, coef: Vec<BigInt>) -> Self {
+        Self::with_context(poly_degree, coef, ())
+    }
+
+    ///
+    /// Create a new polynomial but with a context to the Chinese Remainder Theorem
+    ///
+    pub fn new_with_crt(poly_degree: usize, coef: Vec<BigInt>, crt: &'a Crt) -> Self {
+        Self::new(poly_degree, coef).add_crt(crt)
+    }
+
+    ///
+    /// Add a Chinese Remainder Theorem context
+    ///
+    pub fn add_crt(mut self, crt: &'a Crt) -> Self {
+        self.crt = Some(crt);
+        self
+    }
+
+    ///
+    /// Add a Chinese Remainder Theorem context as an Option
+    ///
+    pub fn add_option_crt(mut self, crt: Option<&'a Crt>) -> Self {
+        self.crt = crt;
+        self
+    }
+
+    ///
+    /// Move the polynomial into evaluation form, using the NTT of every prime of its Chinese
+    /// Remainder Theorem context.
+    ///
+    /// Products of polynomials in evaluation form are slot by slot, so polynomials that are
+    /// multiplied many times (e.g. keys) only need to be transformed once. The result of a product
+    /// still needs to fit in the modulus of the context, as with coefficient form.
+    ///
+    pub fn to_ntt(mut self) -> Self {
+        if self.is_ntt() {
+            return self;
+        }
+        let crt = self
+            .crt
+            .expect("to_ntt: polynomial needs a Chinese Remainder Theorem context");
+        self.evals = self.forward(crt);
+        self.coef = vec![];
+        self.representation = Representation::Evaluation;
+        self
+    }
+
+    ///
+    /// Move the polynomial back into coefficient form, with coefficients in (-M/2, M/2] for the
+    /// modulus M of the Chinese Remainder Theorem context
+    ///
+    pub fn from_ntt(mut self) -> Self {
+        if !self.is_ntt() {
+            return self;
         }
+        let crt = self.crt.unwrap();
+        let mut evals = std::mem::take(&mut self.evals);
+        self.coef = crt.install(|| {
+            evals
+                .par_iter_mut()
+                .zip(&crt.ntts)
+                .for_each(|(x, ntt)| ntt.inverse(x));
+
+            let modulus = &crt.modulus;
+            crt.reconstruct_poly(&evals)
+                .into_par_iter()
+                .map(|x| x.mod_ring(modulus))
+                .collect()
+        });
+        self.representation = Representation::Coefficient;
         self
     }
 
+    ///
+    /// NTT of the coefficients modulo every prime of `crt`
+    ///
+    fn forward(&self, crt: &Crt) -> Vec<Vec<u64>> {
+        if self.is_ntt() {
+            return self.evals.clone();
+        }
+        crt.install(|| {
+            crt.ntts
+                .par_iter()
+                .map(|ntt| {
+                    assert_eq!(
+                        ntt.degree() as usize,
+                        self.poly_degree,
+                        "to_ntt: degree of the Chinese Remainder Theorem context does not match"
+                    );
+                    let mut x = residues(&self.coef, ntt.modulus(), self.poly_degree);
+                    ntt.forward(&mut x);
+                    x
+                })
+                .collect()
+        })
+    }
+
+    ///
+    /// Multiply two polynomials in coefficient form with the given strategy, ignoring any
+    /// Chinese Remainder Theorem context
+    ///
+    pub fn mul_with(&self, other: &PolynomialRing<'a, BigInt>, strategy: MulStrategy) -> Self {
+        assert!(
+            !self.is_ntt() && !other.is_ntt(),
+            "mul_with: polynomials need to be in coefficient form"
+        );
+        let coef = strategy.mul(&self.coef, &other.coef, self.poly_degree);
+        PolynomialRing::new(self.poly_degree, coef).add_option_crt(self.crt)
+    }
+
+    ///
+    /// Largest absolute value of the coefficients. Coefficients are taken as they are, so reduce
+    /// with `% q` first to measure the centered representatives modulo q.
+    ///
+    pub fn inf_norm(&self) -> BigInt {
+        if self.is_ntt() {
+            return self.clone().from_ntt().inf_norm();
+        }
+        self.coef
+            .iter()
+            .map(|c| c.abs())
+            .max()
+            .unwrap_or_else(BigInt::zero)
+    }
+
+    ///
+    /// Euclidean norm of the coefficient vector
+    ///
+    pub fn l2_norm(&self) -> f64 {
+        if self.is_ntt() {
+            return self.clone().from_ntt().l2_norm();
+        }
+        let sum: BigInt = self.coef.iter().map(|c| c * c).sum();
+
+        // Keep the sum of squares within the range of f64 before taking the root
+        let shift = sum.bits().saturating_sub(1000) / 2;
+        let scaled = (sum >> (2 * shift)).to_f64().unwrap();
+        scaled.sqrt() * 2f64.powi(shift as i32)
+    }
+
+    ///
+    /// Number of bits of the largest absolute value of the coefficients
+    ///
+    pub fn bit_size(&self) -> u64 {
+        self.inf_norm().bits()
+    }
+
     ///
     /// Create a random polynomial with samples either 0 or 1.
     ///
@@ -139,11 +491,7 @@ impl<'a> PolynomialRing<'a, BigInt> {
         let coef = (0..size)
             .map(|_| u.sample(&mut rng).to_bigint().unwrap())
             .collect();
-        Self {
-            coef,
-            poly_degree,
-            crt: None,
-        }
+        Self::new(poly_degree, coef)
     }
 
     ///
@@ -160,11 +508,7 @@ impl<'a> PolynomialRing<'a, BigInt> {
                     .unwrap()
             })
             .collect();
-        Self {
-            coef,
-            poly_degree,
-            crt: None,
-        }
+        Self::new(poly_degree, coef)
     }
 
     ///
@@ -176,14 +520,68 @@ impl<'a> PolynomialRing<'a, BigInt> {
         let coef = (0..size)
             .map(|_| n.sample(&mut rng).to_bigint().unwrap())
             .collect();
-        Self {
-            coef,
-            poly_degree,
-            crt: None,
-        }
+        Self::new(poly_degree, coef)
     }
 }
 
+///
+/// Combine two polynomials slot by slot in evaluation form. Operands in coefficient form are
+/// transformed first, using the context of an operand in evaluation form if there is one.
+///
+pub(crate) fn eval_op<'a>(
+    a: &PolynomialRing<'a, BigInt>,
+    b: &PolynomialRing<'a, BigInt>,
+    op: impl Fn(&Modulus, &mut [u64], &[u64]) + Sync,
+) -> PolynomialRing<'a, BigInt> {
+    let crt = if b.is_ntt() { b.crt } else { a.crt }.unwrap();
+
+    let evals = crt.install(|| {
+        let (mut evals, other) = join(|| a.forward(crt), || b.forward(crt));
+        evals
+            .par_iter_mut()
+            .zip(&other)
+            .zip(crt.moduli())
+            .for_each(|((x, y), m)| op(m, x, y));
+        evals
+    });
+
+    a.with_evals(crt, evals)
+}
+
+///
+/// Combine other into a slot by slot in evaluation form, in place. a is moved into evaluation
+/// form first if needed, and other is transformed into a temporary if it is in coefficient form.
+///
+pub(crate) fn eval_op_assign<'a>(
+    a: &mut PolynomialRing<'a, BigInt>,
+    other: &PolynomialRing<'a, BigInt>,
+    op: impl Fn(&Modulus, &mut [u64], &[u64]) + Sync,
+) {
+    let crt = if other.is_ntt() { other.crt } else { a.crt }.unwrap();
+
+    crt.install(|| {
+        if !a.is_ntt() {
+            a.evals = a.forward(crt);
+            a.coef = vec![];
+            a.representation = Representation::Evaluation;
+        }
+        a.crt = Some(crt);
+
+        let transformed;
+        let other = if other.is_ntt() {
+            &other.evals
+        } else {
+            transformed = other.forward(crt);
+            &transformed
+        };
+        a.evals
+            .par_iter_mut()
+            .zip(other)
+            .zip(crt.moduli())
+            .for_each(|((x, y), m)| op(m, x, y));
+    });
+}
+
 impl<'a> std::ops::Rem<&BigInt> for PolynomialRing<'a, BigInt> {
     type Output = Self;
     fn rem(self, other: &BigInt) -> Self::Output {
@@ -194,178 +592,170 @@ impl<'a> std::ops::Rem<&BigInt> for PolynomialRing<'a, BigInt> {
 impl<'a> std::ops::Rem<&BigInt> for &PolynomialRing<'a, BigInt> {
     type Output = PolynomialRing<'a, BigInt>;
     fn rem(self, other: &BigInt) -> Self::Output {
+        if self.is_ntt() {
+            return self.clone().from_ntt() % other;
+        }
         let coef = self.coef.iter().map(|x| x.mod_ring(other)).collect();
         PolynomialRing::new(self.poly_degree, coef).add_option_crt(self.crt)
     }
 }
 
-impl<'a> std::ops::Add for PolynomialRing<'a, BigInt> {
+impl<'a> std::ops::RemAssign<&BigInt> for PolynomialRing<'a, BigInt> {
+    fn rem_assign(&mut self, other: &BigInt) {
+        if self.is_ntt() {
+            *self = &*self % other;
+            return;
+        }
+        self.coef.iter_mut().for_each(|x| *x = x.mod_ring(other));
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::AddAssign<&PolynomialRing<'a, T>> for PolynomialRing<'a, T> {
+    fn add_assign(&mut self, other: &PolynomialRing<'a, T>) {
+        T::poly_add_assign(self, other)
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::AddAssign for PolynomialRing<'a, T> {
+    fn add_assign(&mut self, other: PolynomialRing<'a, T>) {
+        T::poly_add_assign(self, &other)
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::Add<&PolynomialRing<'a, T>> for &PolynomialRing<'a, T> {
+    type Output = PolynomialRing<'a, T>;
+    fn add(self, other: &PolynomialRing<'a, T>) -> Self::Output {
+        T::poly_add(self, other)
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::Add<&PolynomialRing<'a, T>> for PolynomialRing<'a, T> {
+    type Output = PolynomialRing<'a, T>;
+    fn add(mut self, other: &PolynomialRing<'a, T>) -> Self::Output {
+        self += other;
+        self
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::Add for PolynomialRing<'a, T> {
     type Output = Self;
-    fn add(self, other: PolynomialRing<BigInt>) -> Self {
-        let out = other
-            .coef
-            .iter()
-            .zip_longest(self.coef.iter())
-            .map(|x| match x {
-                Both(a, b) => a + b,
-                Left(a) => a.clone(),
-                Right(a) => a.clone(),
-            })
-            .collect();
-        PolynomialRing::new(self.poly_degree, out)
-            .mod_cyc()
-            .add_option_crt(self.crt)
+    fn add(self, other: PolynomialRing<'a, T>) -> Self {
+        self + &other
     }
 }
 
-impl<'a> std::ops::Add<&PolynomialRing<'a, BigInt>> for &PolynomialRing<'a, BigInt> {
-    type Output = PolynomialRing<'a, BigInt>;
-    fn add(self, other: &PolynomialRing<BigInt>) -> Self::Output {
-        let out = other
-            .coef
-            .iter()
-            .zip_longest(self.coef.iter())
-            .map(|x| match x {
-                Both(a, b) => a + b,
-                Left(a) => a.clone(),
-                Right(a) => a.clone(),
-            })
-            .collect();
-        PolynomialRing::new(self.poly_degree, out)
-            .mod_cyc()
-            .add_option_crt(self.crt)
+impl<'a, T: Coefficient> std::ops::Add<PolynomialRing<'a, T>> for &PolynomialRing<'a, T> {
+    type Output = PolynomialRing<'a, T>;
+    fn add(self, other: PolynomialRing<'a, T>) -> Self::Output {
+        // Commutative, so reuse the storage of other
+        other + self
     }
 }
 
-impl<'a> std::ops::Add<&PolynomialRing<'a, BigInt>> for PolynomialRing<'a, BigInt> {
-    type Output = PolynomialRing<'a, BigInt>;
-    fn add(self, other: &PolynomialRing<BigInt>) -> Self::Output {
-        let out = other
-            .coef
-            .iter()
-            .zip_longest(self.coef.iter())
-            .map(|x| match x {
-                Both(a, b) => a + b,
-                Left(a) => a.clone(),
-                Right(a) => a.clone(),
-            })
-            .collect();
-        PolynomialRing::new(self.poly_degree, out)
-            .mod_cyc()
-            .add_option_crt(self.crt)
+impl<'a, T: Coefficient> std::ops::SubAssign<&PolynomialRing<'a, T>> for PolynomialRing<'a, T> {
+    fn sub_assign(&mut self, other: &PolynomialRing<'a, T>) {
+        T::poly_sub_assign(self, other)
     }
 }
 
-impl<'a> std::ops::Sub for PolynomialRing<'a, BigInt> {
+impl<'a, T: Coefficient> std::ops::SubAssign for PolynomialRing<'a, T> {
+    fn sub_assign(&mut self, other: PolynomialRing<'a, T>) {
+        T::poly_sub_assign(self, &other)
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::Sub<&PolynomialRing<'a, T>> for &PolynomialRing<'a, T> {
+    type Output = PolynomialRing<'a, T>;
+    fn sub(self, other: &PolynomialRing<'a, T>) -> Self::Output {
+        T::poly_sub(self, other)
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::Sub<&PolynomialRing<'a, T>> for PolynomialRing<'a, T> {
+    type Output = PolynomialRing<'a, T>;
+    fn sub(mut self, other: &PolynomialRing<'a, T>) -> Self::Output {
+        self -= other;
+        self
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::Sub for PolynomialRing<'a, T> {
     type Output = Self;
-    fn sub(self, other: PolynomialRing<BigInt>) -> Self {
-        let out = other
-            .coef
-            .iter()
-            .zip_longest(self.coef.iter())
-            .map(|x| match x {
-                Both(a, b) => (b - a),
-                Left(a) => a.clone(),
-                Right(a) => a.clone(),
-            })
-            .collect();
-        PolynomialRing::new(self.poly_degree, out)
-            .mod_cyc()
-            .add_option_crt(self.crt)
+    fn sub(self, other: PolynomialRing<'a, T>) -> Self {
+        self - &other
     }
 }
 
-impl<'a> std::ops::Sub<&PolynomialRing<'a, BigInt>> for &PolynomialRing<'a, BigInt> {
-    type Output = PolynomialRing<'a, BigInt>;
-    fn sub(self, other: &PolynomialRing<BigInt>) -> Self::Output {
-        let out = other
-            .coef
-            .iter()
-            .zip_longest(self.coef.iter())
-            .map(|x| match x {
-                Both(a, b) => (b - a),
-                Left(a) => a.clone(),
-                Right(a) => a.clone(),
-            })
-            .collect();
-        PolynomialRing::new(self.poly_degree, out)
-            .mod_cyc()
-            .add_option_crt(self.crt)
+impl<'a, T: Coefficient> std::ops::Sub<PolynomialRing<'a, T>> for &PolynomialRing<'a, T> {
+    type Output = PolynomialRing<'a, T>;
+    fn sub(self, other: PolynomialRing<'a, T>) -> Self::Output {
+        // a - b = -(b - a), reusing the storage of b
+        -(other - self)
     }
 }
 
-impl<'a> std::ops::Sub<&PolynomialRing<'a, BigInt>> for PolynomialRing<'a, BigInt> {
-    type Output = PolynomialRing<'a, BigInt>;
-    fn sub(self, other: &PolynomialRing<BigInt>) -> Self::Output {
-        let out = other
-            .coef
-            .iter()
-            .zip_longest(self.coef.iter())
-            .map(|x| match x {
-                Both(a, b) => (b - a),
-                Left(a) => a.clone(),
-                Right(a) => a.clone(),
-            })
-            .collect();
-        PolynomialRing::new(self.poly_degree, out)
-            .mod_cyc()
-            .add_option_crt(self.crt)
+impl<'a, T: Coefficient> std::ops::MulAssign<&PolynomialRing<'a, T>> for PolynomialRing<'a, T> {
+    fn mul_assign(&mut self, other: &PolynomialRing<'a, T>) {
+        T::poly_mul_assign(self, other)
     }
 }
 
-// TODO: Use FFT to do this in O(nlogn) instead of O(n^2)
-//
-// See; https://math.stackexchange.com/questions/764727/concrete-fft-polynomial-multiplication-example
-impl<'a> std::ops::Mul<&PolynomialRing<'a, BigInt>> for &PolynomialRing<'a, BigInt> {
-    type Output = PolynomialRing<'a, BigInt>;
-    fn mul(self, other: &PolynomialRing<BigInt>) -> Self::Output {
-        if let Some(crt) = self.crt {
-            let a = &self.coef;
-            let b = &other.coef;
-
-            let mul_ntt = |ntt: &Ntt| {
-                let a = ntt.fft_fwd(a);
-                let b = ntt.fft_fwd(b);
-                let c = (0..self.poly_degree).map(|i| &a[i] * &b[i]).collect();
-                let res = ntt
-                    .fft_inv(&c)
-                    .iter()
-                    .map(|x| x.to_bigint().unwrap())
-                    .collect();
-                return PolynomialRing::new(self.poly_degree, res)
-                    .mod_cyc()
-                    .add_option_crt(self.crt);
-            };
-
-            let prod: Vec<PolynomialRing<_>> = crt.ntts.iter().map(|n| mul_ntt(n)).collect();
-
-            let final_coeffs = (0..self.poly_degree)
-                .into_par_iter()
-                .map(|i| {
-                    let vals = prod.iter().map(|p| p.coef[i].to_i128().unwrap()).collect();
-                    crt.reconstruct(vals)
-                        .mod_ring(&crt.modulus.to_bigint().unwrap())
-                })
-                .collect();
+impl<'a, T: Coefficient> std::ops::MulAssign for PolynomialRing<'a, T> {
+    fn mul_assign(&mut self, other: PolynomialRing<'a, T>) {
+        T::poly_mul_assign(self, &other)
+    }
+}
 
-            return PolynomialRing::new(self.poly_degree, final_coeffs)
-                .mod_cyc()
-                .add_option_crt(self.crt);
-        }
-        let mut res = vec![Zero::zero(); other.len() + self.len() - 1];
-        for ((i1, v1), (i2, v2)) in
-            iproduct!(other.coef.iter().enumerate(), self.coef.iter().enumerate())
-        {
-            res[i1 + i2] += v1 * v2;
-        }
-        PolynomialRing::new(self.poly_degree, res)
-            .mod_cyc()
-            .add_option_crt(self.crt)
+impl<'a, T: Coefficient> std::ops::Mul<&PolynomialRing<'a, T>> for &PolynomialRing<'a, T> {
+    type Output = PolynomialRing<'a, T>;
+    fn mul(self, other: &PolynomialRing<'a, T>) -> Self::Output {
+        T::poly_mul(self, other)
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::Mul<&PolynomialRing<'a, T>> for PolynomialRing<'a, T> {
+    type Output = PolynomialRing<'a, T>;
+    fn mul(mut self, other: &PolynomialRing<'a, T>) -> Self::Output {
+        self *= other;
+        self
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::Mul for PolynomialRing<'a, T> {
+    type Output = Self;
+    fn mul(self, other: PolynomialRing<'a, T>) -> Self {
+        self * &other
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::Mul<PolynomialRing<'a, T>> for &PolynomialRing<'a, T> {
+    type Output = PolynomialRing<'a, T>;
+    fn mul(self, other: PolynomialRing<'a, T>) -> Self::Output {
+        // Commutative, so reuse the storage of other
+        other * self
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::Neg for PolynomialRing<'a, T> {
+    type Output = Self;
+    fn neg(mut self) -> Self {
+        self.neg_assign();
+        self
+    }
+}
+
+impl<'a, T: Coefficient> std::ops::Neg for &PolynomialRing<'a, T> {
+    type Output = PolynomialRing<'a, T>;
+    fn neg(self) -> Self::Output {
+        -self.clone()
     }
 }
 
 impl std::fmt::Display for PolynomialRing<'_, BigInt> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.is_ntt() {
+            return write!(f, "{}", self.clone().from_ntt());
+        }
         let coef = &self.coef;
         if coef.is_empty() {
             return write!(f, "0");
@@ -68,6 +68,148 @@ fn mul_crt() {
     println!("{:?}", c);
 }
 
+#[test]
+fn ntt_representation() {
+    use algebra::crt::Crt;
+    use polyr::Representation;
+
+    let poly_degree = 8;
+    let crt = Crt::new(2, 30, poly_degree);
+    let q = 1000.to_bigint().unwrap();
+
+    let a = PolynomialRing::new_with_crt(8, (1..9).map(|x| x.to_bigint().unwrap()).collect(), &crt);
+    let b =
+        PolynomialRing::new_with_crt(8, (0..8).map(|x| (-x).to_bigint().unwrap()).collect(), &crt);
+
+    let a_ntt = a.clone().to_ntt();
+    assert_eq!(a_ntt.representation(), Representation::Evaluation);
+    assert!(a_ntt.coef.is_empty());
+    assert_eq!(a_ntt.clone().from_ntt(), a);
+
+    // Both in evaluation form
+    let b_ntt = b.clone().to_ntt();
+    let prod = &a_ntt * &b_ntt;
+    assert!(prod.is_ntt());
+    assert_eq!(prod.clone().from_ntt(), &a * &b);
+
+    // Mixed forms give evaluation form, and reducing goes back to coefficient form
+    assert_eq!(&a_ntt * &b % &q, &a * &b % &q);
+    assert_eq!((&a + &b_ntt) % &q, (&a + &b) % &q);
+    assert_eq!((&a_ntt - &b).from_ntt(), &a - &b);
+    assert_eq!(((&a_ntt * &b_ntt) + &a).from_ntt(), &a * &b + &a);
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn mul_thread_pool() {
+    use algebra::crt::Crt;
+    use algebra::parallel::{current_num_threads, ThreadPoolBuilder};
+    use std::sync::Arc;
+
+    let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let crt = Crt::new(4, 30, 16).with_thread_pool(Arc::new(pool));
+    assert_eq!(crt.install(current_num_threads), 2);
+
+    let q = (1 << 20).to_bigint().unwrap();
+    let a = PolynomialRing::rand_uniform(&q, 16, 16);
+    let b = PolynomialRing::rand_uniform(&q, 16, 16);
+
+    let expected = &a * &b;
+    let c = &a.clone().add_crt(&crt) * &b;
+    assert_eq!(c.coef, expected.coef);
+}
+
+#[test]
+fn mul_strategies() {
+    use polyr::MulStrategy;
+
+    let q = 1.to_bigint().unwrap() << 200;
+    for &(n, len) in [(4, 4), (8, 3), (128, 128), (256, 200), (96, 96)].iter() {
+        let a = PolynomialRing::rand_uniform(&q, n, len) % &q;
+        let b = PolynomialRing::rand_normal(n, len);
+
+        let expected = a.mul_with(&b, MulStrategy::Schoolbook);
+        assert_eq!(expected.len(), (2 * len - 1).min(n));
+        assert_eq!(a.mul_with(&b, MulStrategy::Karatsuba), expected);
+        assert_eq!(a.mul_with(&b, MulStrategy::Auto), expected);
+        assert_eq!(&a * &b, expected);
+        if n.is_power_of_two() {
+            assert_eq!(a.mul_with(&b, MulStrategy::Ntt), expected);
+        }
+    }
+}
+
+#[test]
+fn automorphism() {
+    use algebra::crt::Crt;
+
+    let n = 8;
+    let crt = Crt::new(2, 30, n as u64);
+    let a = PolynomialRing::new_with_crt(n, (1..9).map(|x| x.to_bigint().unwrap()).collect(), &crt);
+
+    // X -> X^3 sends c_i X^i to c_i X^(3i), with X^8 = -1
+    let expected: Vec<BigInt> = [1, -4, 7, 2, -5, 8, 3, -6]
+        .iter()
+        .map(|x| x.to_bigint().unwrap())
+        .collect();
+    let b = a.automorphism(3);
+    assert_eq!(b.coef, expected);
+
+    // Permuting in evaluation form gives the same polynomial
+    for k in (1..2 * n).step_by(2) {
+        let b_ntt = a.clone().to_ntt().automorphism(k);
+        assert!(b_ntt.is_ntt());
+        assert_eq!(b_ntt.from_ntt(), a.automorphism(k));
+    }
+
+    // Conjugation twice is the identity
+    assert_eq!(a.automorphism(2 * n - 1).automorphism(2 * n - 1), a);
+}
+
+#[test]
+fn mul_monomial() {
+    use algebra::crt::Crt;
+
+    let n = 4;
+    let crt = Crt::new(2, 30, n as u64);
+    let a = PolynomialRing::new_with_crt(n, (1..5).map(|x| x.to_bigint().unwrap()).collect(), &crt);
+
+    let expected = |v: [i32; 4]| v.iter().map(|x| x.to_bigint().unwrap()).collect::<Vec<_>>();
+    assert_eq!(a.mul_monomial(0).coef, expected([1, 2, 3, 4]));
+    assert_eq!(a.mul_monomial(1).coef, expected([-4, 1, 2, 3]));
+    assert_eq!(a.mul_monomial(4).coef, expected([-1, -2, -3, -4]));
+    assert_eq!(a.mul_monomial(7).coef, expected([2, 3, 4, -1]));
+    assert_eq!(a.mul_monomial(8).coef, expected([1, 2, 3, 4]));
+
+    for j in 0..2 * n {
+        let b = a.clone().to_ntt().mul_monomial(j);
+        assert!(b.is_ntt());
+        assert_eq!(b.from_ntt(), a.mul_monomial(j));
+    }
+}
+
+#[test]
+fn norms() {
+    let a = PolynomialRing::new(
+        4,
+        [3, -4, 0, 2]
+            .iter()
+            .map(|x| x.to_bigint().unwrap())
+            .collect(),
+    );
+    assert_eq!(a.inf_norm(), 4.to_bigint().unwrap());
+    assert_eq!(a.bit_size(), 3);
+    assert!((a.l2_norm() - 29f64.sqrt()).abs() < 1e-12);
+
+    let q: BigInt = BigInt::one() << 800;
+    let big = PolynomialRing::new(4, vec![BigInt::one() - &q, q.clone()]);
+    assert_eq!(big.inf_norm(), q);
+    assert_eq!(big.bit_size(), 801);
+    assert!((big.l2_norm().log2() - 800.5).abs() < 1e-9);
+
+    assert_eq!(PolynomialRing::new(4, vec![]).inf_norm(), BigInt::zero());
+}
+
 #[test]
 fn sub() {
     let a = PolynomialRing::new(4, vec![One::one(); 4]);
@@ -193,3 +335,233 @@ fn overflow_mul() {
         c
     );
 }
+
+#[test]
+fn generic_coefficients() {
+    use algebra::modulus::Modulus;
+    use num_traits::ToPrimitive;
+
+    let n = 16;
+    let a: Vec<i64> = (0..n as i64).map(|i| (i * 37) % 23 - 11).collect();
+    let b: Vec<i64> = (0..10).map(|i| (i * 13) % 17 - 8).collect();
+
+    let big = |x: &[i64]| PolynomialRing::new(n, x.iter().map(|&c| BigInt::from(c)).collect());
+    let (a_big, b_big) = (big(&a), big(&b));
+
+    // Integers in i64 agree with BigInt
+    let a_int = PolynomialRing::with_context(n, a.clone(), ());
+    let b_int = PolynomialRing::with_context(n, b.clone(), ());
+    let to_big = |p: PolynomialRing<i64>| big(&p.coef);
+    assert_eq!(to_big(&a_int + &b_int), &a_big + &b_big);
+    assert_eq!(to_big(&b_int - &a_int), &b_big - &a_big);
+    assert_eq!(to_big(&a_int * &b_int), &a_big * &b_big);
+
+    // u64 modulo p agrees with BigInt reduced modulo p
+    let p = 97u64;
+    let modulus = Modulus::new(p);
+    let reduce = |x: &[i64]| x.iter().map(|&c| c.rem_euclid(p as i64) as u64).collect();
+    let a_mod = PolynomialRing::with_context(n, reduce(&a), modulus);
+    let b_mod = PolynomialRing::with_context(n, reduce(&b), modulus);
+    let q = BigInt::from(p);
+    let check = |x: PolynomialRing<u64>, y: PolynomialRing<BigInt>| {
+        let y = y % &q;
+        let y: Vec<u64> = y
+            .coef
+            .iter()
+            .map(|c| c.to_i64().unwrap().rem_euclid(p as i64) as u64)
+            .collect();
+        assert_eq!(x.coef, y);
+    };
+    check(&a_mod + &b_mod, &a_big + &b_big);
+    check(&b_mod - &a_mod, &b_big - &a_big);
+    check(&a_mod * &b_mod, &a_big * &b_big);
+
+    // Residue vectors are the u64 results modulo every prime
+    let moduli = vec![Modulus::new(p), Modulus::new(101)];
+    let residues = |x: &[i64]| -> Vec<Vec<u64>> {
+        x.iter()
+            .map(|&c| {
+                moduli
+                    .iter()
+                    .map(|m| c.rem_euclid(m.value() as i64) as u64)
+                    .collect()
+            })
+            .collect()
+    };
+    let a_rns = PolynomialRing::with_context(n, residues(&a), moduli.clone());
+    let b_rns = PolynomialRing::with_context(n, residues(&b), moduli.clone());
+    let prod = &a_rns * &b_rns;
+    let first: Vec<u64> = prod.coef.iter().map(|c| c[0]).collect();
+    assert_eq!(first, (&a_mod * &b_mod).coef);
+    assert_eq!(prod.context(), &moduli);
+}
+
+#[test]
+fn polynomial_algebra() {
+    use num_rational::Ratio;
+
+    // (x^3 - 2x^2 - 4) = (x^2 + x + 3)(x - 3) + 5
+    let a: Polynomial<i64> = polynomial![-4, 0, -2, 1];
+    let b = polynomial![-3, 1];
+    let (q, r) = a.div_rem(&b);
+    assert_eq!(polynomial![3, 1, 1], q);
+    assert_eq!(polynomial![5], r);
+    assert_eq!(a, &(&q * &b) + &r);
+
+    assert_eq!(-20, a.eval(&-2));
+    assert_eq!(polynomial![0, -4, 3], a.derivative());
+    // a(x + 1) = x^3 + x^2 - x - 5
+    assert_eq!(polynomial![-5, -1, 1, 1], a.compose(&polynomial![1, 1]));
+
+    let r = |n| Ratio::from_integer(n);
+    let a = polynomial![r(-1), r(0), r(1)];
+    let b = polynomial![r(2), r(4), r(2)];
+    assert_eq!(polynomial![r(1), r(1)], a.gcd(&b));
+
+    // 2x^2 + 3 through three of its points
+    let xs = [r(-1), r(2), r(5)];
+    let ys: Vec<_> = xs.iter().map(|x| r(2) * x * x + r(3)).collect();
+    assert_eq!(
+        polynomial![r(3), r(0), r(2)],
+        Polynomial::interpolate(&xs, &ys)
+    );
+}
+
+#[test]
+fn polynomial_fft_mul() {
+    let a: Vec<i64> = (0..100).map(|i| (i * 37) % 101 - 50).collect();
+    let b: Vec<i64> = (0..80).map(|i| (i * 13) % 29 - 14).collect();
+
+    let mut expected = vec![0; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            expected[i + j] += x * y;
+        }
+    }
+
+    let prod = Polynomial::new(a.clone()) * Polynomial::new(b.clone());
+    assert_eq!(Polynomial::new(expected.clone()), prod);
+
+    let float = |x: &[i64]| Polynomial::new(x.iter().map(|&c| c as f64).collect());
+    let prod = float(&a) * float(&b);
+    for (x, y) in prod.coef().iter().zip(&expected) {
+        assert!((x - *y as f64).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn polynomial_small_int_mul() {
+    // Short products stay on the machine integers
+    let a: Polynomial<i32> = polynomial![1, -2, 3];
+    let b = polynomial![4, 5];
+    assert_eq!(polynomial![4, -3, 2, 15], &a * &b);
+
+    let a: Polynomial<i64> = polynomial![i64::MAX / 2, 1];
+    assert_eq!(polynomial![i64::MAX - 1, 2], &a * &polynomial![2]);
+}
+
+#[test]
+fn polynomial_float_division() {
+    // (x - 0.1)(x - 0.3) and (x - 0.1)(x - 0.7), whose remainders only vanish up to rounding
+    let a: Polynomial<f64> = polynomial![0.03, -0.4, 1.];
+    let b = polynomial![0.07, -0.8, 1.];
+    let gcd = a.gcd(&b);
+    assert_eq!(2, gcd.len());
+    assert!((gcd.coef()[0] + 0.1).abs() < 1e-9);
+    assert!((gcd.coef()[1] - 1.).abs() < 1e-12);
+
+    // A tiny leading coefficient is still a leading coefficient
+    let a: Polynomial<f64> = polynomial![1., 2., 3.];
+    let divisor = polynomial![1., 1e-12];
+    let (q, r) = a.div_rem(&divisor);
+    assert_eq!(2, q.len());
+    assert_eq!(1, r.len());
+    // Up to rounding relative to the terms, which are about 3e24
+    let back = &(&q * &divisor) + &r;
+    for (x, y) in back.coef().iter().zip(a.coef()) {
+        assert!((x - y).abs() <= 1e-9 * r.coef()[0].abs());
+    }
+
+    // So is a tiny constant
+    let (q, r) = a.div_rem(&polynomial![1e-10]);
+    assert!(r.is_empty());
+    assert!((q.coef()[2] - 3e10).abs() < 1.);
+}
+
+#[test]
+fn approximation() {
+    use polyr::Approximation;
+
+    let f = |x: f64| x.exp();
+    let cheb = Approximation::chebyshev(f, 0., 2., 10);
+    assert!(cheb.max_error() < 1e-8);
+
+    // Remez levels the error, so it can only improve on the interpolant
+    let cheb = Approximation::chebyshev(f, 0., 2., 4);
+    let remez = Approximation::remez(f, 0., 2., 4);
+    assert!(remez.max_error() <= cheb.max_error());
+    assert!(remez.max_error() < 5e-3);
+
+    // The power basis evaluates to the same polynomial
+    let power = remez.power_basis();
+    assert_eq!(power.degree(), 4);
+    for i in 0..=20 {
+        let x = i as f64 / 10.;
+        assert!((power.eval(&x) - remez.eval(x)).abs() < 1e-9);
+        assert!((f(x) - remez.eval(x)).abs() <= remez.max_error() + 1e-12);
+    }
+
+    // A polynomial of the degree is approximated exactly
+    let g = |x: f64| 3. * x * x * x - x + 0.5;
+    let exact = Approximation::remez(g, -4., 1., 3);
+    assert!(exact.max_error() < 1e-9);
+    let coef = exact.power_basis();
+    for (c, expected) in coef.coef().iter().zip(&[0.5, -1., 0., 3.]) {
+        assert!((c - expected).abs() < 1e-9);
+    }
+
+    // Minimax of |x| on [-1, 1] by a quadratic is x^2 + 1/8
+    let abs = Approximation::remez(f64::abs, -1., 1., 2);
+    assert!((abs.max_error() - 0.125).abs() < 1e-4);
+}
+
+#[test]
+fn assign_ops() {
+    use algebra::crt::Crt;
+
+    let n = 8;
+    let q: BigInt = BigInt::one() << 40;
+    let a = PolynomialRing::new(n, (1..=8).map(|x| BigInt::from(x * 1000)).collect());
+    let b = PolynomialRing::new(n, (1..=5).map(|x| BigInt::from(-x * 77)).collect());
+
+    let mut c = a.clone();
+    c += &b;
+    assert_eq!(c, &a + &b);
+    c -= &b;
+    assert_eq!(c, a);
+
+    // A longer other is negated, not copied
+    let mut c = b.clone();
+    c -= &a;
+    assert_eq!(c, -(&a - &b));
+    assert_eq!(&b - a.clone(), c);
+    assert_eq!(b.clone() + &a, &a + b.clone());
+
+    let mut c = a.clone();
+    c *= &b;
+    assert_eq!(c, &a * &b);
+    assert_eq!(
+        -c.clone() + &c,
+        PolynomialRing::new(n, vec![BigInt::zero(); n])
+    );
+
+    // In place slot by slot in evaluation form
+    let crt = Crt::new(3, 30, n as u64);
+    let a_crt = a.clone().add_crt(&crt);
+    let mut c = a_crt.clone().to_ntt();
+    c *= &b;
+    c += &a_crt;
+    c = -c;
+    assert!(c.is_ntt());
+    assert_eq!(c % &q, -(&a * &b + &a) % &q);
+}
@@ -22,7 +22,6 @@ fn keygen_benchmark(c: &mut Criterion) {
 
 fn keygen_relin_benchmark(c: &mut Criterion) {
     let ciph_modulus = 1.to_bigint().unwrap() << 600;
-    let big_modulus = 1.to_bigint().unwrap() << 1200;
 
     let mut group = c.benchmark_group("keygen_relin");
     for i in 1..13 {
@@ -30,7 +29,7 @@ fn keygen_relin_benchmark(c: &mut Criterion) {
         let key = Rwle::keygen(&ciph_modulus, poly_degree as usize, poly_degree as usize);
 
         group.bench_with_input(BenchmarkId::from_parameter(poly_degree), &key, |b, k| {
-            b.iter(|| k.relin_key(&big_modulus));
+            b.iter(|| k.relin_key(3));
         });
     }
     group.finish()
@@ -106,14 +105,13 @@ fn addition_benchmark(c: &mut Criterion) {
 fn multiplication_benchmark(c: &mut Criterion) {
     let ciph_modulus = 1.to_bigint().unwrap() << 600;
     let scaling_factor = 1_usize << 30;
-    let big_modulus = 1.to_bigint().unwrap() << 1200;
 
     let mut group = c.benchmark_group("multiplication");
     group.sample_size(10);
     for i in 1..13 {
         let poly_degree = 1 << i;
         let key = Rwle::keygen(&ciph_modulus, poly_degree as usize, poly_degree as usize);
-        let relin_key = key.relin_key(&big_modulus);
+        let relin_key = key.relin_key(3);
         let encoder = encoder::CKKSEncoder::new(poly_degree as usize * 2);
         let msg = vec![0f64; poly_degree >> 1];
         let plain = encode(&msg, scaling_factor, &encoder);
@@ -126,7 +124,7 @@ fn multiplication_benchmark(c: &mut Criterion) {
             |b| {
                 b.iter(|| {
                     let mult = &cipher * &cipher;
-                    mult.relin(&relin_key, &big_modulus);
+                    mult.relin(&relin_key);
                 })
             },
         );
@@ -0,0 +1,362 @@
+use num_bigint::BigInt;
+
+use algebra::modulus::Modulus;
+use algebra::ntt::Ntt;
+use algebra::prime::is_prime;
+use polyr::{Modulo, PolynomialRing};
+
+use crate::{div_round, key_switch, Error, KeySwitchKey, PublicKey};
+
+///
+/// The BFV scheme, for exact arithmetic on integers modulo a plaintext modulus t.
+///
+/// A message m in Z_t[X]/(X^N + 1) is encrypted as c0 + c1 * s = Δ * m + e (mod q) with
+/// Δ = floor(q / t), so it sits in the high bits and decryption rounds the noise e away as long
+/// as it stays below Δ / 2. Keys are the ones of `Rwle::keygen`, and products are relinearized
+/// with `Rwle::relin_key`.
+///
+/// When t is a prime that is 1 (mod 2N), X^N + 1 splits into N linear factors modulo t, and a
+/// plaintext holds N independent slots of Z_t that add and multiply slot by slot (batching).
+///
+#[derive(Debug)]
+pub struct Bfv {
+    modulus: BigInt,
+    plain_modulus: Modulus,
+    poly_degree: usize,
+    // NTT modulo t, when t allows batching
+    ntt: Option<Ntt>,
+}
+
+///
+/// A polynomial with coefficients modulo the plaintext modulus t
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlainText<'n> {
+    pub poly: PolynomialRing<'n, u64>,
+}
+
+///
+/// c0 + c1 * s = Δ * m + e (mod q)
+///
+#[derive(Debug, Clone)]
+pub struct CipherText<'n> {
+    c: [PolynomialRing<'n, BigInt>; 2],
+    modulus: BigInt,
+}
+
+impl<'n> CipherText<'n> {
+    pub fn modulus(&self) -> &BigInt {
+        &self.modulus
+    }
+}
+
+impl Bfv {
+    ///
+    /// BFV with ciphertext modulus q and plaintext modulus t, for polynomials of degree N
+    ///
+    pub fn new(modulus: &BigInt, plain_modulus: u64, poly_degree: usize) -> Self {
+        assert!(
+            poly_degree.is_power_of_two(),
+            "Bfv: the polynomial degree needs to be a power of 2"
+        );
+        assert!(
+            plain_modulus > 1,
+            "Bfv: the plaintext modulus needs to be at least 2"
+        );
+        assert!(
+            *modulus > BigInt::from(plain_modulus),
+            "Bfv: the ciphertext modulus needs to be larger than the plaintext modulus"
+        );
+
+        let order = 2 * poly_degree as u64;
+        let rem = (plain_modulus - 1) % order;
+        let ntt = if rem == 0 && is_prime(plain_modulus) {
+            Some(Ntt::new(poly_degree as u64, plain_modulus))
+        } else {
+            None
+        };
+
+        Bfv {
+            modulus: modulus.clone(),
+            plain_modulus: Modulus::new(plain_modulus),
+            poly_degree,
+            ntt,
+        }
+    }
+
+    pub fn modulus(&self) -> &BigInt {
+        &self.modulus
+    }
+
+    pub fn plain_modulus(&self) -> u64 {
+        self.plain_modulus.value()
+    }
+
+    pub fn poly_degree(&self) -> usize {
+        self.poly_degree
+    }
+
+    ///
+    /// Number of batching slots, N when t allows batching and 0 otherwise
+    ///
+    pub fn num_slots(&self) -> usize {
+        if self.ntt.is_some() {
+            self.poly_degree
+        } else {
+            0
+        }
+    }
+
+    ///
+    /// Δ = floor(q / t)
+    ///
+    pub fn delta(&self) -> BigInt {
+        &self.modulus / self.plain_modulus()
+    }
+
+    ///
+    /// Encode values modulo t directly as the coefficients of a plaintext. Missing coefficients
+    /// are zero.
+    ///
+    pub fn encode_coeffs<'n>(&self, values: &[u64]) -> Result<PlainText<'n>, Error> {
+        if values.len() > self.poly_degree {
+            return Err(Error::TooManyCoefficients {
+                len: values.len(),
+                poly_degree: self.poly_degree,
+            });
+        }
+        let mut coef: Vec<u64> = values
+            .iter()
+            .map(|&x| self.plain_modulus.reduce(x))
+            .collect();
+        coef.resize(self.poly_degree, 0);
+        Ok(self.plaintext(coef))
+    }
+
+    pub fn decode_coeffs(&self, plain: &PlainText) -> Vec<u64> {
+        let mut coef = plain.poly.coef.clone();
+        coef.resize(self.poly_degree, 0);
+        coef
+    }
+
+    ///
+    /// Encode values modulo t into the N slots of a plaintext, so that products and sums of
+    /// plaintexts are slot by slot. Missing values are zero.
+    ///
+    pub fn encode<'n>(&self, values: &[u64]) -> Result<PlainText<'n>, Error> {
+        let ntt = self.batching()?;
+        if values.len() > self.poly_degree {
+            return Err(Error::TooManyValues {
+                len: values.len(),
+                num_slots: self.poly_degree,
+            });
+        }
+        let mut coef: Vec<u64> = values
+            .iter()
+            .map(|&x| self.plain_modulus.reduce(x))
+            .collect();
+        coef.resize(self.poly_degree, 0);
+        ntt.inverse(&mut coef);
+        Ok(self.plaintext(coef))
+    }
+
+    ///
+    /// The N slots of a plaintext, see `encode`
+    ///
+    pub fn decode(&self, plain: &PlainText) -> Result<Vec<u64>, Error> {
+        let ntt = self.batching()?;
+        let mut values = self.decode_coeffs(plain);
+        ntt.forward(&mut values);
+        Ok(values)
+    }
+
+    pub fn encrypt<'n>(&self, pk: &PublicKey<'n, BigInt>, plain: &PlainText) -> CipherText<'n> {
+        let modulus = &self.modulus;
+        let poly_degree = self.poly_degree;
+
+        let e1 = PolynomialRing::rand_normal(poly_degree, poly_degree);
+        let e2 = PolynomialRing::rand_normal(poly_degree, poly_degree);
+        let u = PolynomialRing::rand_binary(poly_degree, poly_degree);
+
+        let c0 = ((&pk.0 * &u) % modulus + &e1 + self.scale_up(plain)) % modulus;
+        let c1 = (&pk.1 * &u + &e2) % modulus;
+
+        CipherText {
+            c: [c0, c1],
+            modulus: modulus.clone(),
+        }
+    }
+
+    ///
+    /// round(t / q * (c0 + c1 * s)) modulo t
+    ///
+    pub fn decrypt<'n>(
+        &self,
+        sk: &PolynomialRing<'n, BigInt>,
+        ct: &CipherText<'n>,
+    ) -> PlainText<'n> {
+        let modulus = &ct.modulus;
+        let t = BigInt::from(self.plain_modulus());
+
+        let noisy = (&ct.c[0] + &(&ct.c[1] * sk)) % modulus;
+        let coef = noisy
+            .coef
+            .iter()
+            .map(|x| {
+                let m = div_round(&(x * &t), modulus);
+                self.plain_modulus.reduce_bigint(&m)
+            })
+            .collect();
+        self.plaintext(coef)
+    }
+
+    ///
+    /// How many bits the noise is below Δ / 2, so 0 means that the ciphertext may no longer
+    /// decrypt correctly
+    ///
+    pub fn noise_budget<'n>(&self, sk: &PolynomialRing<'n, BigInt>, ct: &CipherText<'n>) -> u64 {
+        let plain = self.decrypt(sk, ct);
+        let noisy = (&ct.c[0] + &(&ct.c[1] * sk)) % &ct.modulus;
+        let noise = (noisy - self.scale_up(&plain)) % &ct.modulus;
+        let half_delta: BigInt = self.delta() >> 1;
+        half_delta.bits().saturating_sub(noise.inf_norm().bits())
+    }
+
+    pub fn add_plain<'n>(&self, ct: &CipherText<'n>, plain: &PlainText) -> CipherText<'n> {
+        let mut res = ct.clone();
+        res.c[0] = (&res.c[0] + &self.scale_up(plain)) % &res.modulus;
+        res
+    }
+
+    ///
+    /// Multiply by a plaintext, which multiplies the noise by about t N / 2
+    ///
+    pub fn mul_plain<'n>(&self, ct: &CipherText<'n>, plain: &PlainText) -> CipherText<'n> {
+        let m = self.lift(plain);
+        let modulus = &ct.modulus;
+        CipherText {
+            c: [(&ct.c[0] * &m) % modulus, (&ct.c[1] * &m) % modulus],
+            modulus: modulus.clone(),
+        }
+    }
+
+    ///
+    /// The product of two ciphertexts, relinearized with a key from `Rwle::relin_key`.
+    ///
+    /// The components are multiplied exactly over the integers and scaled by t / q. A Chinese
+    /// Remainder Theorem context is dropped for this product, since it would reduce it modulo
+    /// its primes, and put back on the result.
+    ///
+    pub fn mul<'n>(
+        &self,
+        a: &CipherText<'n>,
+        b: &CipherText<'n>,
+        relin_key: &KeySwitchKey,
+    ) -> CipherText<'n> {
+        let modulus = &a.modulus;
+        let t = BigInt::from(self.plain_modulus());
+
+        let crt = a.c[0].crt();
+        let poly_degree = self.poly_degree;
+
+        // Centered coefficients, without the context
+        let exact =
+            |x: &PolynomialRing<'n, BigInt>| PolynomialRing::new(poly_degree, (x % modulus).coef);
+        // round(t / q * x) modulo q, for every coefficient
+        let scale_down = |x: PolynomialRing<'n, BigInt>| {
+            let coef = x
+                .coef
+                .iter()
+                .map(|c| div_round(&(c * &t), modulus).mod_ring(modulus))
+                .collect();
+            PolynomialRing::new(poly_degree, coef).add_option_crt(crt)
+        };
+
+        let (a0, a1) = (exact(&a.c[0]), exact(&a.c[1]));
+        let (b0, b1) = (exact(&b.c[0]), exact(&b.c[1]));
+
+        let d0 = scale_down(&a0 * &b0);
+        let d1 = scale_down(&a0 * &b1 + &a1 * &b0);
+        let d2 = scale_down(&a1 * &b1);
+
+        let (ks0, ks1) = key_switch(&d2, relin_key);
+
+        CipherText {
+            c: [(d0 + ks0) % modulus, (d1 + ks1) % modulus],
+            modulus: modulus.clone(),
+        }
+    }
+
+    fn batching(&self) -> Result<&Ntt, Error> {
+        self.ntt.as_ref().ok_or(Error::NoBatching {
+            plain_modulus: self.plain_modulus(),
+            poly_degree: self.poly_degree,
+        })
+    }
+
+    fn plaintext<'n>(&self, coef: Vec<u64>) -> PlainText<'n> {
+        PlainText {
+            poly: PolynomialRing::with_context(self.poly_degree, coef, self.plain_modulus),
+        }
+    }
+
+    ///
+    /// The plaintext as integers centered modulo t, which keeps products with it small
+    ///
+    fn lift<'n>(&self, plain: &PlainText) -> PolynomialRing<'n, BigInt> {
+        let coef = plain
+            .poly
+            .coef
+            .iter()
+            .map(|&x| BigInt::from(self.plain_modulus.center(x)))
+            .collect();
+        PolynomialRing::new(self.poly_degree, coef)
+    }
+
+    ///
+    /// Δ * m
+    ///
+    fn scale_up<'n>(&self, plain: &PlainText) -> PolynomialRing<'n, BigInt> {
+        let delta = self.delta();
+        let coef = plain.poly.coef.iter().map(|&x| &delta * x).collect();
+        PolynomialRing::new(self.poly_degree, coef)
+    }
+}
+
+impl<'n> std::ops::Add for &CipherText<'n> {
+    type Output = CipherText<'n>;
+    fn add(self, other: &CipherText<'n>) -> Self::Output {
+        let modulus = &self.modulus;
+        CipherText {
+            c: [
+                (&self.c[0] + &other.c[0]) % modulus,
+                (&self.c[1] + &other.c[1]) % modulus,
+            ],
+            modulus: modulus.clone(),
+        }
+    }
+}
+
+impl<'n> std::ops::Sub for &CipherText<'n> {
+    type Output = CipherText<'n>;
+    fn sub(self, other: &CipherText<'n>) -> Self::Output {
+        let modulus = &self.modulus;
+        CipherText {
+            c: [
+                (&self.c[0] - &other.c[0]) % modulus,
+                (&self.c[1] - &other.c[1]) % modulus,
+            ],
+            modulus: modulus.clone(),
+        }
+    }
+}
+
+impl<'n> std::ops::Neg for &CipherText<'n> {
+    type Output = CipherText<'n>;
+    fn neg(self) -> Self::Output {
+        CipherText {
+            c: [-&self.c[0], -&self.c[1]],
+            modulus: self.modulus.clone(),
+        }
+    }
+}
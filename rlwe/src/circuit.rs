@@ -0,0 +1,573 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use num_bigint::BigInt;
+use num_traits::Zero;
+use polyr::Approximation;
+
+use crate::{CipherText, Error, Evaluator};
+
+type Ct<'n> = CipherText<'n, BigInt, 2>;
+
+// Interval on which `Expr::sigmoid` is accurate, and the degree of its approximation
+const SIGMOID_BOUND: f64 = 8.;
+const SIGMOID_DEGREE: usize = 3;
+
+///
+/// An expression over named encrypted inputs, constants and plaintext vectors, e.g.
+///
+/// `(Expr::input("x") * Expr::input("w") + Expr::plain(&b)).sigmoid()`
+///
+/// Expressions are cheap to clone, and clones are shared: a subexpression used twice is computed
+/// once. `Plan::compile` turns expressions into the operations to run on ciphertexts.
+///
+#[derive(Debug, Clone)]
+pub struct Expr(Rc<Node>);
+
+#[derive(Debug)]
+enum Node {
+    Input(String),
+    Const(f64),
+    Plain(Vec<f64>),
+    Add(Expr, Expr),
+    Sub(Expr, Expr),
+    Mul(Expr, Expr),
+    Neg(Expr),
+    Rotate(Expr, usize),
+}
+
+impl Expr {
+    ///
+    /// The encrypted input `name`, given to `Plan::execute`
+    ///
+    pub fn input(name: &str) -> Self {
+        Expr(Rc::new(Node::Input(name.to_string())))
+    }
+
+    ///
+    /// The same value in every slot
+    ///
+    pub fn constant(c: f64) -> Self {
+        Expr(Rc::new(Node::Const(c)))
+    }
+
+    ///
+    /// Unencrypted values, one per slot. Missing values are zero.
+    ///
+    pub fn plain(values: &[f64]) -> Self {
+        Expr(Rc::new(Node::Plain(values.to_vec())))
+    }
+
+    ///
+    /// Rotate the slots to the left
+    ///
+    pub fn rotate(&self, rotation: usize) -> Self {
+        Expr(Rc::new(Node::Rotate(self.clone(), rotation)))
+    }
+
+    pub fn square(&self) -> Self {
+        self * self
+    }
+
+    ///
+    /// c_0 + c_1 x + ... + c_d x^d, with the powers computed by a tree of products so this uses
+    /// ceil(log2(d)) + 1 levels
+    ///
+    pub fn polynomial(&self, coef: &[f64]) -> Self {
+        let degree = coef.iter().rposition(|&c| c != 0.).unwrap_or(0);
+
+        // x^k = x^(2^j) * x^(k - 2^j) for the largest 2^j < k
+        let mut powers = vec![Expr::constant(1.), self.clone()];
+        for k in 2..=degree {
+            let high = 1 << (usize::BITS - 1 - (k - 1).leading_zeros());
+            let power = &powers[high] * &powers[k - high];
+            powers.push(power);
+        }
+
+        let mut res = Expr::constant(coef.first().copied().unwrap_or(0.));
+        for (c, power) in coef.iter().zip(&powers).take(degree + 1).skip(1) {
+            if *c != 0. {
+                res = res + power * *c;
+            }
+        }
+        res
+    }
+
+    ///
+    /// Evaluate a polynomial approximation, accurate up to its max error on its interval
+    ///
+    pub fn approximate(&self, approximation: &Approximation) -> Self {
+        self.polynomial(approximation.power_basis().coef())
+    }
+
+    ///
+    /// 1 / (1 + e^-x), approximated by a polynomial of degree 3 on [-8, 8]
+    ///
+    pub fn sigmoid(&self) -> Self {
+        let sigmoid = |x: f64| 1. / (1. + (-x).exp());
+        let approximation =
+            Approximation::remez(sigmoid, -SIGMOID_BOUND, SIGMOID_BOUND, SIGMOID_DEGREE);
+        self.approximate(&approximation)
+    }
+}
+
+macro_rules! expr_op {
+    ($trait:ident, $method:ident, $node:ident) => {
+        impl std::ops::$trait<&Expr> for &Expr {
+            type Output = Expr;
+            fn $method(self, other: &Expr) -> Expr {
+                Expr(Rc::new(Node::$node(self.clone(), other.clone())))
+            }
+        }
+
+        impl std::ops::$trait for Expr {
+            type Output = Expr;
+            fn $method(self, other: Expr) -> Expr {
+                Expr(Rc::new(Node::$node(self, other)))
+            }
+        }
+
+        impl std::ops::$trait<&Expr> for Expr {
+            type Output = Expr;
+            fn $method(self, other: &Expr) -> Expr {
+                Expr(Rc::new(Node::$node(self, other.clone())))
+            }
+        }
+
+        impl std::ops::$trait<Expr> for &Expr {
+            type Output = Expr;
+            fn $method(self, other: Expr) -> Expr {
+                Expr(Rc::new(Node::$node(self.clone(), other)))
+            }
+        }
+
+        impl std::ops::$trait<f64> for Expr {
+            type Output = Expr;
+            fn $method(self, other: f64) -> Expr {
+                Expr(Rc::new(Node::$node(self, Expr::constant(other))))
+            }
+        }
+
+        impl std::ops::$trait<f64> for &Expr {
+            type Output = Expr;
+            fn $method(self, other: f64) -> Expr {
+                Expr(Rc::new(Node::$node(self.clone(), Expr::constant(other))))
+            }
+        }
+
+        impl std::ops::$trait<Expr> for f64 {
+            type Output = Expr;
+            fn $method(self, other: Expr) -> Expr {
+                Expr(Rc::new(Node::$node(Expr::constant(self), other)))
+            }
+        }
+    };
+}
+
+expr_op!(Add, add, Add);
+expr_op!(Sub, sub, Sub);
+expr_op!(Mul, mul, Mul);
+
+impl std::ops::Neg for Expr {
+    type Output = Expr;
+    fn neg(self) -> Expr {
+        Expr(Rc::new(Node::Neg(self)))
+    }
+}
+
+impl std::ops::Neg for &Expr {
+    type Output = Expr;
+    fn neg(self) -> Expr {
+        -self.clone()
+    }
+}
+
+///
+/// One step of a `Plan`. Every instruction writes a new register, numbered by its position, and
+/// reads the registers of earlier instructions.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Input(String),
+    Add(usize, usize),
+    Sub(usize, usize),
+    Negate(usize),
+    AddConst(usize, f64),
+    AddPlain(usize, Vec<f64>),
+    /// Product of two ciphertexts, with 3 components
+    Multiply(usize, usize),
+    /// Product with a plaintext, at the product of the scales
+    MulPlain(usize, Vec<f64>),
+    /// Product with an integer constant, which keeps the scale
+    MulInteger(usize, i64),
+    /// Back to 2 components after a `Multiply`
+    Relinearize(usize),
+    /// Divide the scale and the modulus by Δ
+    Rescale(usize),
+    /// Divide the modulus by Δ^levels, to match the operand of an `Add`, `Sub` or `Multiply`
+    LevelDown(usize, usize),
+    Rotate(usize, usize),
+}
+
+///
+/// Expressions compiled into instructions for ciphertexts of a given number of slots.
+///
+/// Compiling keeps every ciphertext at the scaling factor Δ: products are followed by a
+/// relinearization and a rescale, and operands at different levels are brought down to the
+/// lower one first. Products with integer constants are exact and need no rescale. Expressions
+/// on plaintexts and constants alone are folded.
+///
+#[derive(Debug, Clone)]
+pub struct Plan {
+    instructions: Vec<Instruction>,
+    // Number of rescales before every register
+    levels: Vec<usize>,
+    inputs: Vec<String>,
+    outputs: Vec<(String, usize)>,
+    num_slots: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Cipher(usize),
+    Plain(Vec<f64>),
+}
+
+enum Register<'n> {
+    Two(Ct<'n>),
+    Three(CipherText<'n, BigInt, 3>),
+}
+
+impl Plan {
+    ///
+    /// Compile named outputs. Panics if an output does not depend on an input, or a plaintext
+    /// has more than `num_slots` values.
+    ///
+    pub fn compile(outputs: &[(&str, Expr)], num_slots: usize) -> Self {
+        let mut plan = Plan {
+            instructions: vec![],
+            levels: vec![],
+            inputs: vec![],
+            outputs: vec![],
+            num_slots,
+        };
+
+        let mut memo = HashMap::new();
+        for (name, expr) in outputs {
+            match plan.value(expr, &mut memo) {
+                Value::Cipher(reg) => plan.outputs.push((name.to_string(), reg)),
+                Value::Plain(_) => panic!("compile: the output {} has no input", name),
+            }
+        }
+        plan
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    ///
+    /// Names of the inputs, in the order they first appear
+    ///
+    pub fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    pub fn num_slots(&self) -> usize {
+        self.num_slots
+    }
+
+    ///
+    /// Number of rescales on the longest path, so the modulus needs to be at least Δ^depth times
+    /// the precision wanted for the outputs
+    ///
+    pub fn depth(&self) -> usize {
+        self.levels.iter().copied().max().unwrap_or(0)
+    }
+
+    ///
+    /// Rotations the plan needs keys for, modulo the number of slots
+    ///
+    pub fn rotations(&self) -> Vec<usize> {
+        let mut rotations: Vec<usize> = self
+            .instructions
+            .iter()
+            .filter_map(|ins| match ins {
+                Instruction::Rotate(_, r) => Some(*r),
+                _ => None,
+            })
+            .collect();
+        rotations.sort_unstable();
+        rotations.dedup();
+        rotations
+    }
+
+    ///
+    /// An `Evaluator` with the relinearization key and exactly the rotation keys of the plan
+    ///
+    pub fn evaluator<'n>(
+        &self,
+        key: &crate::Rwle<'n, BigInt>,
+        scaling_factor: usize,
+        dnum: usize,
+    ) -> Evaluator {
+        Evaluator::new(key, scaling_factor, dnum, &self.rotations())
+    }
+
+    ///
+    /// Run the plan on a ciphertext at the scaling factor of the evaluator for every input
+    ///
+    pub fn execute<'n>(
+        &self,
+        eval: &Evaluator,
+        inputs: &HashMap<&str, Ct<'n>>,
+    ) -> Result<HashMap<String, Ct<'n>>, Error> {
+        let mut regs: Vec<Register<'n>> = Vec::with_capacity(self.instructions.len());
+        let two = |regs: &[Register<'n>], i: usize| match &regs[i] {
+            Register::Two(ct) => ct.clone(),
+            Register::Three(_) => unreachable!("execute: register {} is not relinearized", i),
+        };
+
+        for ins in &self.instructions {
+            let res = match ins {
+                Instruction::Input(name) => {
+                    let ct = inputs
+                        .get(name.as_str())
+                        .ok_or(Error::MissingInput { name: name.clone() })?;
+                    self.check_input(eval, ct)?;
+                    Register::Two(ct.clone())
+                }
+                Instruction::Add(a, b) => {
+                    Register::Two(eval.add(&two(&regs, *a), &two(&regs, *b))?)
+                }
+                Instruction::Sub(a, b) => {
+                    Register::Two(eval.sub(&two(&regs, *a), &two(&regs, *b))?)
+                }
+                Instruction::Negate(a) => Register::Two(eval.negate(&two(&regs, *a))),
+                Instruction::AddConst(a, c) => Register::Two(eval.add_const(&two(&regs, *a), *c)),
+                Instruction::AddPlain(a, values) => {
+                    Register::Two(eval.add_plain(&two(&regs, *a), values)?)
+                }
+                Instruction::Multiply(a, b) => {
+                    let (a, b) = (two(&regs, *a), two(&regs, *b));
+                    let (a, b) = eval.align(&a, &b)?;
+                    Register::Three(a.tensor(b.as_ref()))
+                }
+                Instruction::MulPlain(a, values) => {
+                    let a = two(&regs, *a);
+                    let plain = eval.encode(values, a.num_slots, eval.scaling_factor())?;
+                    Register::Two(a * &plain)
+                }
+                Instruction::MulInteger(a, k) => {
+                    Register::Two(eval.mul_integer(&two(&regs, *a), *k))
+                }
+                Instruction::Relinearize(a) => match &regs[*a] {
+                    Register::Three(ct) => Register::Two(ct.relin(eval.relin_key())),
+                    Register::Two(ct) => Register::Two(ct.clone()),
+                },
+                Instruction::Rescale(a) => Register::Two(eval.rescale(&two(&regs, *a))?),
+                Instruction::LevelDown(a, levels) => {
+                    let a = two(&regs, *a);
+                    let division = num_traits::pow(eval.scaling_factor().clone(), *levels);
+                    let rem = &a.modulus % &division;
+                    if !rem.is_zero() || a.modulus <= division {
+                        return Err(Error::OutOfLevels { modulus: a.modulus });
+                    }
+                    Register::Two(eval.level_down(&a, &(&a.modulus / &division)))
+                }
+                Instruction::Rotate(a, r) => Register::Two(eval.rotate(&two(&regs, *a), *r)?),
+            };
+            regs.push(res);
+        }
+
+        Ok(self
+            .outputs
+            .iter()
+            .map(|(name, reg)| (name.clone(), two(&regs, *reg)))
+            .collect())
+    }
+
+    fn check_input(&self, eval: &Evaluator, ct: &Ct) -> Result<(), Error> {
+        if ct.num_slots != self.num_slots {
+            return Err(Error::SlotCountMismatch {
+                expected: self.num_slots,
+                found: ct.num_slots,
+            });
+        }
+        let scaling_factor = eval.scaling_factor().to_biguint().unwrap();
+        if ct.scaling_factor != scaling_factor {
+            return Err(Error::ScaleMismatch {
+                left: ct.scaling_factor.clone(),
+                right: scaling_factor,
+            });
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, ins: Instruction, level: usize) -> usize {
+        self.instructions.push(ins);
+        self.levels.push(level);
+        self.instructions.len() - 1
+    }
+
+    fn value(&mut self, expr: &Expr, memo: &mut HashMap<*const Node, Value>) -> Value {
+        let key = Rc::as_ptr(&expr.0);
+        if let Some(value) = memo.get(&key) {
+            return value.clone();
+        }
+
+        let n = self.num_slots;
+        let value = match &*expr.0 {
+            Node::Input(name) => {
+                // The same name in different expressions is the same input
+                let found = self.instructions.iter().position(|ins| match ins {
+                    Instruction::Input(other) => other == name,
+                    _ => false,
+                });
+                Value::Cipher(found.unwrap_or_else(|| {
+                    self.inputs.push(name.clone());
+                    self.emit(Instruction::Input(name.clone()), 0)
+                }))
+            }
+            Node::Const(c) => Value::Plain(vec![*c; n]),
+            Node::Plain(values) => {
+                assert!(
+                    values.len() <= n,
+                    "compile: {} values do not fit in {} slots",
+                    values.len(),
+                    n
+                );
+                let mut values = values.clone();
+                values.resize(n, 0.);
+                Value::Plain(values)
+            }
+            Node::Neg(a) => {
+                let a = self.value(a, memo);
+                self.negate(a)
+            }
+            Node::Rotate(a, rotation) => {
+                let rotation = rotation % n;
+                match self.value(a, memo) {
+                    Value::Cipher(a) if rotation == 0 => Value::Cipher(a),
+                    Value::Cipher(a) => {
+                        Value::Cipher(self.emit(Instruction::Rotate(a, rotation), self.levels[a]))
+                    }
+                    Value::Plain(mut a) => {
+                        a.rotate_left(rotation);
+                        Value::Plain(a)
+                    }
+                }
+            }
+            Node::Add(a, b) => {
+                let (a, b) = (self.value(a, memo), self.value(b, memo));
+                self.add(a, b)
+            }
+            Node::Sub(a, b) => {
+                let (a, b) = (self.value(a, memo), self.value(b, memo));
+                match (a, b) {
+                    (Value::Cipher(a), Value::Cipher(b)) => {
+                        let (a, b) = self.align(a, b);
+                        Value::Cipher(self.emit(Instruction::Sub(a, b), self.levels[a]))
+                    }
+                    (a, b) => {
+                        let b = self.negate(b);
+                        self.add(a, b)
+                    }
+                }
+            }
+            Node::Mul(a, b) => {
+                let (a, b) = (self.value(a, memo), self.value(b, memo));
+                self.mul(a, b)
+            }
+        };
+
+        memo.insert(key, value.clone());
+        value
+    }
+
+    fn negate(&mut self, a: Value) -> Value {
+        match a {
+            Value::Cipher(a) => Value::Cipher(self.emit(Instruction::Negate(a), self.levels[a])),
+            Value::Plain(a) => Value::Plain(a.iter().map(|x| -x).collect()),
+        }
+    }
+
+    fn add(&mut self, a: Value, b: Value) -> Value {
+        match (a, b) {
+            (Value::Plain(a), Value::Plain(b)) => {
+                Value::Plain(a.iter().zip(&b).map(|(x, y)| x + y).collect())
+            }
+            (Value::Cipher(a), Value::Plain(b)) | (Value::Plain(b), Value::Cipher(a)) => {
+                let level = self.levels[a];
+                if b.iter().all(|&x| x == 0.) {
+                    Value::Cipher(a)
+                } else if b.iter().all(|&x| x == b[0]) {
+                    Value::Cipher(self.emit(Instruction::AddConst(a, b[0]), level))
+                } else {
+                    Value::Cipher(self.emit(Instruction::AddPlain(a, b), level))
+                }
+            }
+            (Value::Cipher(a), Value::Cipher(b)) => {
+                let (a, b) = self.align(a, b);
+                Value::Cipher(self.emit(Instruction::Add(a, b), self.levels[a]))
+            }
+        }
+    }
+
+    fn mul(&mut self, a: Value, b: Value) -> Value {
+        match (a, b) {
+            (Value::Plain(a), Value::Plain(b)) => {
+                Value::Plain(a.iter().zip(&b).map(|(x, y)| x * y).collect())
+            }
+            (Value::Cipher(a), Value::Plain(b)) | (Value::Plain(b), Value::Cipher(a)) => {
+                let level = self.levels[a];
+                match integer(&b) {
+                    Some(1) => Value::Cipher(a),
+                    // Exact, so no rescale and no level
+                    Some(k) => Value::Cipher(self.emit(Instruction::MulInteger(a, k), level)),
+                    None => {
+                        let product = self.emit(Instruction::MulPlain(a, b), level);
+                        Value::Cipher(self.emit(Instruction::Rescale(product), level + 1))
+                    }
+                }
+            }
+            (Value::Cipher(a), Value::Cipher(b)) => {
+                let (a, b) = self.align(a, b);
+                let level = self.levels[a];
+                let product = self.emit(Instruction::Multiply(a, b), level);
+                let relin = self.emit(Instruction::Relinearize(product), level);
+                Value::Cipher(self.emit(Instruction::Rescale(relin), level + 1))
+            }
+        }
+    }
+
+    ///
+    /// Bring the operand with fewer rescales down to the level of the other
+    ///
+    fn align(&mut self, a: usize, b: usize) -> (usize, usize) {
+        let (level_a, level_b) = (self.levels[a], self.levels[b]);
+        if level_a < level_b {
+            let a = self.emit(Instruction::LevelDown(a, level_b - level_a), level_b);
+            (a, b)
+        } else if level_b < level_a {
+            let b = self.emit(Instruction::LevelDown(b, level_a - level_b), level_a);
+            (a, b)
+        } else {
+            (a, b)
+        }
+    }
+}
+
+///
+/// The integer in every slot, when the values are all the same integer
+///
+fn integer(values: &[f64]) -> Option<i64> {
+    let k = *values.first()?;
+    // Beyond 2^53 a float no longer tells the integers apart
+    let uniform = values.iter().all(|&x| x == k);
+    if uniform && k.fract() == 0. && k.abs() < (1_u64 << 53) as f64 {
+        Some(k as i64)
+    } else {
+        None
+    }
+}
@@ -3,6 +3,8 @@ use std::f64::consts::PI;
 
 use algebra::utils::bit_reverse_vec;
 
+use crate::Error;
+
 ///
 /// Algorithms from "Improved Bootstrapping for Approximate Homomorphic Encryption"
 ///
@@ -49,6 +51,35 @@ impl CKKSEncoder {
         }
     }
 
+    ///
+    /// Degree N of the polynomial ring, where the fft length is 2N
+    ///
+    pub fn poly_degree(&self) -> usize {
+        self.fft_length >> 1
+    }
+
+    ///
+    /// Maximum number of complex values that fit in a plaintext, N/2
+    ///
+    pub fn num_slots(&self) -> usize {
+        self.fft_length >> 2
+    }
+
+    ///
+    /// Distance between the coefficients holding consecutive slots when only `num_slots` slots
+    /// are used, i.e. the values live in the subring of polynomials in X^gap.
+    ///
+    pub fn slot_gap(&self, num_slots: usize) -> Result<usize, Error> {
+        let max_slots = self.num_slots();
+        if !num_slots.is_power_of_two() || num_slots > max_slots {
+            return Err(Error::InvalidSlotCount {
+                num_slots,
+                max_slots,
+            });
+        }
+        Ok(max_slots / num_slots)
+    }
+
     pub fn embedding(&self, coeffs: &Vec<Complex64>) -> Vec<Complex64> {
         assert!(coeffs.len() <= self.fft_length);
 
@@ -0,0 +1,106 @@
+use num_bigint::{BigInt, BigUint};
+
+///
+/// Errors returned by the CKKS scheme
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// More values were given than there are slots in a plaintext
+    TooManyValues { len: usize, num_slots: usize },
+    /// More values were given than there are coefficients in a plaintext
+    TooManyCoefficients { len: usize, poly_degree: usize },
+    /// The number of slots is not a power of two between 1 and N/2
+    InvalidSlotCount { num_slots: usize, max_slots: usize },
+    /// The scaling factors of two ciphertexts cannot be aligned without losing precision
+    ScaleMismatch { left: BigUint, right: BigUint },
+    /// A scaling factor would be multiplied by zero, which leaves nothing to decode
+    ZeroScale,
+    /// Neither modulus of two ciphertexts divides the other
+    ModulusMismatch { left: BigInt, right: BigInt },
+    /// The modulus of a ciphertext is too small to rescale it once more
+    OutOfLevels { modulus: BigInt },
+    /// Rescaling divides the modulus by the scaling factor, which it is not a multiple of, e.g. a
+    /// modulus made of Chinese Remainder Theorem primes with a power of two as the scale
+    ModulusNotMultipleOfScale {
+        modulus: BigInt,
+        scaling_factor: BigInt,
+    },
+    /// No rotation key was created for the rotation
+    MissingRotationKey { rotation: usize },
+    /// A circuit was executed without a ciphertext for one of its inputs
+    MissingInput { name: String },
+    /// A plaintext or ciphertext holds a different number of slots than expected, e.g. than a
+    /// circuit was compiled for
+    SlotCountMismatch { expected: usize, found: usize },
+    /// Batching needs a prime plaintext modulus that is 1 (mod 2N)
+    NoBatching {
+        plain_modulus: u64,
+        poly_degree: usize,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::TooManyValues { len, num_slots } => write!(
+                f,
+                "cannot encode {} values into a plaintext with {} slots",
+                len, num_slots
+            ),
+            Error::TooManyCoefficients { len, poly_degree } => write!(
+                f,
+                "cannot encode {} coefficients into a plaintext of degree {}",
+                len, poly_degree
+            ),
+            Error::InvalidSlotCount {
+                num_slots,
+                max_slots,
+            } => write!(
+                f,
+                "{} slots is not a power of two of at most {}",
+                num_slots, max_slots
+            ),
+            Error::ScaleMismatch { left, right } => write!(
+                f,
+                "cannot combine ciphertexts with scaling factors {} and {}",
+                left, right
+            ),
+            Error::ZeroScale => write!(f, "cannot multiply a scaling factor by zero"),
+            Error::ModulusMismatch { left, right } => write!(
+                f,
+                "cannot bring ciphertexts with moduli {} and {} to a common modulus",
+                left, right
+            ),
+            Error::OutOfLevels { modulus } => write!(
+                f,
+                "a ciphertext with modulus {} cannot be rescaled",
+                modulus
+            ),
+            Error::ModulusNotMultipleOfScale {
+                modulus,
+                scaling_factor,
+            } => write!(
+                f,
+                "the modulus {} is not a multiple of the scaling factor {}, so it cannot be rescaled",
+                modulus, scaling_factor
+            ),
+            Error::MissingRotationKey { rotation } => {
+                write!(f, "there is no key for a rotation by {}", rotation)
+            }
+            Error::MissingInput { name } => write!(f, "no ciphertext for the input {}", name),
+            Error::SlotCountMismatch { expected, found } => {
+                write!(f, "expected {} slots, found {}", expected, found)
+            }
+            Error::NoBatching {
+                plain_modulus,
+                poly_degree,
+            } => write!(
+                f,
+                "{} is not a prime that is 1 (mod 2N) for N = {}, so there are no slots",
+                plain_modulus, poly_degree
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,260 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use num_bigint::{BigInt, BigUint, ToBigInt};
+use num_complex::Complex64;
+use num_traits::{ToPrimitive, Zero};
+
+use polyr::Modulo;
+
+use crate::encoder::CKKSEncoder;
+use crate::{encode_sparse, CipherText, Error, KeySwitchKey, PlainText, Rwle, ScaleAlignment};
+
+type Ct<'n> = CipherText<'n, BigInt, 2>;
+
+///
+/// Evaluates circuits on ciphertexts of 2 components, holding the keys the operations need.
+///
+/// Ciphertexts are kept at the scaling factor Δ of the evaluator: products are relinearized and
+/// rescaled by Δ right away, which divides the modulus by Δ, so every product uses up one level.
+/// Operations on two ciphertexts first bring both to the same scale and the lower of their moduli.
+///
+/// Rescaling is exact, so the ciphertext modulus needs to be a multiple of a power of Δ, e.g. a
+/// power of two with Δ a power of two. Over a modulus made of Chinese Remainder Theorem primes,
+/// the operations that rescale return `Error::ModulusNotMultipleOfScale`, while the others work.
+///
+pub struct Evaluator {
+    encoder: CKKSEncoder,
+    scaling_factor: BigInt,
+    relin_key: KeySwitchKey,
+    conjugation_key: KeySwitchKey,
+    // Keyed by the rotation modulo N/2
+    rotation_keys: HashMap<usize, KeySwitchKey>,
+}
+
+impl<'n> Evaluator {
+    ///
+    /// Create the relinearization and conjugation keys, and a rotation key for every one of
+    /// `rotations`, all with `dnum` digits
+    ///
+    pub fn new(
+        key: &Rwle<'n, BigInt>,
+        scaling_factor: usize,
+        dnum: usize,
+        rotations: &[usize],
+    ) -> Self {
+        let poly_degree = key.sk.poly_degree;
+        let num_slots = poly_degree / 2;
+
+        let rotation_keys = rotations
+            .iter()
+            .map(|r| r % num_slots)
+            .filter(|&r| r != 0)
+            .map(|r| (r, key.rotation_key(r, dnum)))
+            .collect();
+
+        Self {
+            encoder: CKKSEncoder::new(2 * poly_degree),
+            scaling_factor: scaling_factor.to_bigint().unwrap(),
+            relin_key: key.relin_key(dnum),
+            conjugation_key: key.conjugation_key(dnum),
+            rotation_keys,
+        }
+    }
+
+    pub fn encoder(&self) -> &CKKSEncoder {
+        &self.encoder
+    }
+
+    pub fn scaling_factor(&self) -> &BigInt {
+        &self.scaling_factor
+    }
+
+    pub(crate) fn relin_key(&self) -> &KeySwitchKey {
+        &self.relin_key
+    }
+
+    ///
+    /// Rotations there is a key for, modulo N/2
+    ///
+    pub fn rotations(&self) -> Vec<usize> {
+        let mut rotations: Vec<usize> = self.rotation_keys.keys().copied().collect();
+        rotations.sort_unstable();
+        rotations
+    }
+
+    pub fn add(&self, a: &Ct<'n>, b: &Ct<'n>) -> Result<Ct<'n>, Error> {
+        let (a, b) = self.align(a, b)?;
+        Ok(a.as_ref() + b.as_ref())
+    }
+
+    pub fn sub(&self, a: &Ct<'n>, b: &Ct<'n>) -> Result<Ct<'n>, Error> {
+        let (a, b) = self.align(a, b)?;
+        Ok(a.as_ref() - b.as_ref())
+    }
+
+    pub fn negate(&self, a: &Ct<'n>) -> Ct<'n> {
+        -a
+    }
+
+    ///
+    /// a * b, relinearized and rescaled, one level below the lower of the two
+    ///
+    pub fn mul(&self, a: &Ct<'n>, b: &Ct<'n>) -> Result<Ct<'n>, Error> {
+        let (a, b) = self.align(a, b)?;
+        self.check_level(&a)?;
+        self.check_scale(&(&a.scaling_factor * &b.scaling_factor))?;
+        let product: CipherText<'n, BigInt, 3> = a.tensor(b.as_ref());
+        self.rescale(&product.relin(&self.relin_key))
+    }
+
+    pub fn square(&self, a: &Ct<'n>) -> Result<Ct<'n>, Error> {
+        self.mul(a, a)
+    }
+
+    ///
+    /// Multiply every slot by the matching value, rescaled like `mul`. Missing values are zero.
+    ///
+    pub fn mul_plain(&self, a: &Ct<'n>, values: &[f64]) -> Result<Ct<'n>, Error> {
+        self.check_level(a)?;
+        let plain = self.encode(values, a.num_slots, &self.scaling_factor)?;
+        self.rescale(&(a * &plain))
+    }
+
+    ///
+    /// Multiply every slot by the integer k. The coefficients are multiplied by k and the scale
+    /// is kept, so unlike `mul_plain` this is exact and uses no level.
+    ///
+    pub fn mul_integer(&self, a: &Ct<'n>, k: i64) -> Ct<'n> {
+        a.mul_coefficients(&BigInt::from(k))
+    }
+
+    ///
+    /// Add c to every slot. A constant is encoded by the constant polynomial round(c * scale),
+    /// so this uses no level.
+    ///
+    pub fn add_const(&self, a: &Ct<'n>, c: f64) -> Ct<'n> {
+        let scale = a.scaling_factor.to_f64().unwrap();
+        let constant = (c * scale).round().to_bigint().unwrap();
+
+        let mut res = a.clone();
+        let mut c0 = &res.c[0] % &res.modulus;
+        c0.coef.resize(c0.poly_degree, BigInt::zero());
+        c0.coef[0] = (&c0.coef[0] + constant).mod_ring(&res.modulus);
+        res.c[0] = c0;
+        res
+    }
+
+    ///
+    /// Add the matching value to every slot, encoded at the scale of the ciphertext. Missing
+    /// values are zero.
+    ///
+    pub fn add_plain(&self, a: &Ct<'n>, values: &[f64]) -> Result<Ct<'n>, Error> {
+        let scaling_factor = a.scaling_factor.to_bigint().unwrap();
+        let plain = self.encode(values, a.num_slots, &scaling_factor)?;
+
+        let mut res = a.clone();
+        res.c[0] = (&res.c[0] + &plain.poly) % &res.modulus;
+        Ok(res)
+    }
+
+    ///
+    /// Rotate the slots to the left. Needs a key for the rotation modulo N/2.
+    ///
+    pub fn rotate(&self, a: &Ct<'n>, rotation: usize) -> Result<Ct<'n>, Error> {
+        let rotation = rotation % a.num_slots;
+        if rotation == 0 {
+            return Ok(a.clone());
+        }
+        let key = self
+            .rotation_keys
+            .get(&rotation)
+            .ok_or(Error::MissingRotationKey { rotation })?;
+        Ok(a.rotate(rotation, key))
+    }
+
+    pub fn conjugate(&self, a: &Ct<'n>) -> Ct<'n> {
+        a.conjugate(&self.conjugation_key)
+    }
+
+    ///
+    /// Bring a ciphertext down to a lower modulus, e.g. the one of a ciphertext it is combined
+    /// with. Keeps the scale.
+    ///
+    /// Panics unless `modulus` divides the modulus of the ciphertext.
+    ///
+    pub fn level_down(&self, a: &Ct<'n>, modulus: &BigInt) -> Ct<'n> {
+        a.lower_modulus(modulus)
+    }
+
+    ///
+    /// Divide the scale and the modulus by Δ
+    ///
+    pub fn rescale(&self, a: &Ct<'n>) -> Result<Ct<'n>, Error> {
+        self.check_level(a)?;
+        self.check_scale(&a.scaling_factor)?;
+        Ok(a.rescale(&self.scaling_factor))
+    }
+
+    ///
+    /// Real values encoded into `num_slots` slots
+    ///
+    pub(crate) fn encode(
+        &self,
+        values: &[f64],
+        num_slots: usize,
+        scaling_factor: &BigInt,
+    ) -> Result<PlainText<'n, BigInt>, Error> {
+        let values: Vec<Complex64> = values.iter().map(|&x| Complex64::new(x, 0.)).collect();
+        let scaling_factor = scaling_factor.to_usize().unwrap();
+        encode_sparse(&values, num_slots, scaling_factor, &self.encoder)
+    }
+
+    ///
+    /// A ciphertext can be rescaled while its modulus is a multiple of Δ larger than Δ
+    ///
+    fn check_level<const N: usize>(&self, a: &CipherText<'n, BigInt, N>) -> Result<(), Error> {
+        let rem = &a.modulus % &self.scaling_factor;
+        if !rem.is_zero() {
+            return Err(Error::ModulusNotMultipleOfScale {
+                modulus: a.modulus.clone(),
+                scaling_factor: self.scaling_factor.clone(),
+            });
+        }
+        if a.modulus <= self.scaling_factor {
+            return Err(Error::OutOfLevels {
+                modulus: a.modulus.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    ///
+    /// Rescaling divides the scale by Δ, so it needs to be a multiple of Δ. This fails e.g. for
+    /// the product of operands aligned by multiplying to a scale that is not.
+    ///
+    fn check_scale(&self, scaling_factor: &BigUint) -> Result<(), Error> {
+        let delta = self.scaling_factor.to_biguint().unwrap();
+        let rem = scaling_factor % &delta;
+        if !rem.is_zero() {
+            return Err(Error::ScaleMismatch {
+                left: scaling_factor.clone(),
+                right: delta,
+            });
+        }
+        Ok(())
+    }
+
+    ///
+    /// Both ciphertexts at the same scale and the lower of their moduli. Scales are aligned by
+    /// rescaling when possible, which heads back to Δ, and by multiplying otherwise.
+    ///
+    pub(crate) fn align<'c>(
+        &self,
+        a: &'c Ct<'n>,
+        b: &'c Ct<'n>,
+    ) -> Result<(Cow<'c, Ct<'n>>, Cow<'c, Ct<'n>>), Error> {
+        a.align_scale(b, ScaleAlignment::Rescale)
+            .or_else(|_| a.align_scale(b, ScaleAlignment::Multiply))
+    }
+}
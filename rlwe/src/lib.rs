@@ -1,72 +1,762 @@
 #![feature(int_log)]
+pub mod bfv;
+pub mod circuit;
 pub mod encoder;
+pub mod error;
+pub mod evaluator;
+pub mod norm;
+pub mod precision;
 
+pub use circuit::{Expr, Plan};
 use encoder::CKKSEncoder;
+pub use error::Error;
+pub use evaluator::Evaluator;
+pub use norm::CanonicalNorm;
 
-use polyr::{Modulo, PolynomialRing};
+use polyr::{Coefficient, Modulo, PolynomialRing};
 
 use num_bigint::{BigInt, BigUint, ToBigInt, ToBigUint};
 use num_complex::Complex64;
 use num_rational::Ratio;
 use num_traits::cast::ToPrimitive;
-use num_traits::Zero;
+use num_traits::{One, Signed, Zero};
 
-use algebra::crt::Crt;
+use algebra::crt::{Crt, ExtendedBasis};
+use algebra::parallel::*;
+use algebra::prime::{ntt_primes, NttPrimes};
 
 use arrayvec::ArrayVec;
 
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::ops::Range;
+
 // b & a from equation a * s + e = b where a,s,e are randomly generated
 #[derive(Debug)]
-pub struct PublicKey<'n, T>(pub PolynomialRing<'n, T>, pub PolynomialRing<'n, T>);
+pub struct PublicKey<'n, T: Coefficient>(pub PolynomialRing<'n, T>, pub PolynomialRing<'n, T>);
 // encrypted data
-#[derive(Debug)]
-pub struct CipherText<'n, T, const N: usize> {
+#[derive(Debug, Clone)]
+pub struct CipherText<'n, T: Coefficient, const N: usize> {
     c: ArrayVec<PolynomialRing<'n, T>, N>,
     scaling_factor: BigUint,
     modulus: BigInt,
+    num_slots: usize,
 }
 
-impl<T, const N: usize> CipherText<'_, T, N> {
+impl<T: Coefficient, const N: usize> CipherText<'_, T, N> {
     pub fn dim(&self) -> usize {
         self.c.len()
     }
+
+    ///
+    /// Modulus q of the components, which rescaling and `lower_modulus` divide
+    ///
+    pub fn modulus(&self) -> &BigInt {
+        &self.modulus
+    }
+
+    ///
+    /// Scale of the encrypted values, which products multiply and rescaling divides
+    ///
+    pub fn scaling_factor(&self) -> &BigUint {
+        &self.scaling_factor
+    }
+
+    ///
+    /// Number of slots that hold values, N/2 unless the values are sparsely packed
+    ///
+    pub fn num_slots(&self) -> usize {
+        self.num_slots
+    }
+}
+
+impl<'n, const N: usize> CipherText<'n, BigInt, N> {
+    ///
+    /// Multiply with a ciphertext of any dimension. The result decrypts with (1, s, s^2, ...) and
+    /// has dim a + dim b - 1 components, so the capacity M of the result type needs to be at
+    /// least that:
+    ///
+    /// `let z: CipherText<BigInt, 4> = x.tensor(&y);` for x of dim 3 and y of dim 2
+    ///
+    /// Panics if M is too small.
+    ///
+    pub fn tensor<const B: usize, const M: usize>(
+        &self,
+        other: &CipherText<'n, BigInt, B>,
+    ) -> CipherText<'n, BigInt, M> {
+        let dim = self.dim() + other.dim() - 1;
+        assert!(
+            dim <= M,
+            "tensor: the product has {} components but the ciphertext holds at most {}",
+            dim,
+            M
+        );
+
+        let modulus = self.modulus.clone();
+        let c = tensor(&self.c, &other.c, &modulus);
+
+        CipherText {
+            c: c.into_iter().collect(),
+            modulus,
+            scaling_factor: &self.scaling_factor * &other.scaling_factor,
+            num_slots: self.num_slots,
+        }
+    }
+
+    ///
+    /// Divide the components, the modulus and the scaling factor by `division`, rounding the
+    /// components. This brings the scale of a product Δ^2 back to Δ, and the noise down with it.
+    ///
+    /// Panics unless `division` divides both the modulus and the scaling factor.
+    ///
+    pub fn rescale(&self, division: &BigInt) -> Self {
+        let division_u = division
+            .to_biguint()
+            .expect("rescale: division needs to be positive");
+        assert!(
+            (&self.modulus % division).is_zero() && (&self.scaling_factor % &division_u).is_zero(),
+            "rescale: division needs to divide the modulus and the scaling factor"
+        );
+        let modulus = &self.modulus / division;
+
+        let c = self
+            .c
+            .iter()
+            .map(|x| {
+                let mut x = x % &self.modulus;
+                x.coef = x.coef.iter().map(|c| div_round(c, division)).collect();
+                x % &modulus
+            })
+            .collect();
+
+        CipherText {
+            c,
+            modulus,
+            scaling_factor: &self.scaling_factor / &division_u,
+            num_slots: self.num_slots,
+        }
+    }
+
+    ///
+    /// Reduce the components modulo a divisor of the modulus, keeping the scaling factor. Two
+    /// ciphertexts need the same modulus before they can be combined.
+    ///
+    /// Panics unless `modulus` divides the modulus of the ciphertext.
+    ///
+    pub fn lower_modulus(&self, modulus: &BigInt) -> Self {
+        assert!(
+            (&self.modulus % modulus).is_zero(),
+            "lower_modulus: the new modulus needs to divide the modulus"
+        );
+        CipherText {
+            c: self.c.iter().map(|x| x % modulus).collect(),
+            modulus: modulus.clone(),
+            scaling_factor: self.scaling_factor.clone(),
+            num_slots: self.num_slots,
+        }
+    }
+
+    ///
+    /// Multiply the encrypted values by an integer, without changing them: the scaling factor is
+    /// multiplied by |k| too. For a negative k the values are negated, since the scale stays
+    /// positive. This is exact and uses no modulus.
+    ///
+    /// Returns an error for k = 0, which would leave a scaling factor of zero.
+    ///
+    pub fn mul_integer(&self, k: &BigInt) -> Result<Self, Error> {
+        if k.is_zero() {
+            return Err(Error::ZeroScale);
+        }
+        let mut res = self.mul_coefficients(&k.abs());
+        res.scaling_factor *= k.magnitude();
+        Ok(if k.is_negative() { -res } else { res })
+    }
+
+    ///
+    /// Multiply the components by an integer k and keep the scaling factor, which multiplies the
+    /// encrypted values by k
+    ///
+    pub(crate) fn mul_coefficients(&self, k: &BigInt) -> Self {
+        let modulus = &self.modulus;
+        let c = self
+            .c
+            .iter()
+            .map(|x| {
+                let mut x = x % modulus;
+                x.coef = x.coef.iter().map(|c| (c * k).mod_ring(modulus)).collect();
+                x
+            })
+            .collect();
+
+        CipherText {
+            c,
+            modulus: modulus.clone(),
+            scaling_factor: self.scaling_factor.clone(),
+            num_slots: self.num_slots,
+        }
+    }
+
+    ///
+    /// Bring two ciphertexts to the same scaling factor and modulus, so they can be added.
+    ///
+    /// The scaling factors need to be an integer ratio r apart. With `ScaleAlignment::Multiply`
+    /// the side with the smaller scale is multiplied by r, and with `ScaleAlignment::Rescale` the
+    /// side with the larger scale is rescaled by r. Afterwards the larger modulus is lowered to
+    /// the smaller one.
+    ///
+    /// Returns an error when the ratio is not an integer, when r does not divide the modulus to
+    /// rescale, or when neither modulus divides the other, as any of these would lose precision.
+    ///
+    pub fn align_scale<'c>(
+        &'c self,
+        other: &'c Self,
+        alignment: ScaleAlignment,
+    ) -> Result<(Cow<'c, Self>, Cow<'c, Self>), Error> {
+        let swap = self.scaling_factor > other.scaling_factor;
+        let (small, large) = if swap { (other, self) } else { (self, other) };
+
+        let (small, large) = if small.scaling_factor == large.scaling_factor {
+            (Cow::Borrowed(small), Cow::Borrowed(large))
+        } else {
+            let mismatch = || Error::ScaleMismatch {
+                left: self.scaling_factor.clone(),
+                right: other.scaling_factor.clone(),
+            };
+
+            let rem = &large.scaling_factor % &small.scaling_factor;
+            if !rem.is_zero() {
+                return Err(mismatch());
+            }
+            let ratio = (&large.scaling_factor / &small.scaling_factor)
+                .to_bigint()
+                .unwrap();
+
+            match alignment {
+                ScaleAlignment::Multiply => {
+                    (Cow::Owned(small.mul_integer(&ratio)?), Cow::Borrowed(large))
+                }
+                ScaleAlignment::Rescale => {
+                    let rem = &large.modulus % &ratio;
+                    if !rem.is_zero() || large.modulus <= ratio {
+                        return Err(mismatch());
+                    }
+                    (Cow::Borrowed(small), Cow::Owned(large.rescale(&ratio)))
+                }
+            }
+        };
+
+        let (small, large) = match small.modulus.cmp(&large.modulus) {
+            Ordering::Equal => (small, large),
+            Ordering::Less if (&large.modulus % &small.modulus).is_zero() => {
+                let large = Cow::Owned(large.lower_modulus(&small.modulus));
+                (small, large)
+            }
+            Ordering::Greater if (&small.modulus % &large.modulus).is_zero() => {
+                let small = Cow::Owned(small.lower_modulus(&large.modulus));
+                (small, large)
+            }
+            _ => {
+                return Err(Error::ModulusMismatch {
+                    left: self.modulus.clone(),
+                    right: other.modulus.clone(),
+                })
+            }
+        };
+
+        Ok(if swap { (large, small) } else { (small, large) })
+    }
+
+    ///
+    /// self + other, after bringing both to the same scaling factor and modulus with
+    /// `align_scale`. The sum is at the larger scale with `ScaleAlignment::Multiply`, and at the
+    /// smaller one with `ScaleAlignment::Rescale`.
+    ///
+    pub fn add_aligned(&self, other: &Self, alignment: ScaleAlignment) -> Result<Self, Error> {
+        let (a, b) = self.align_scale(other, alignment)?;
+        Ok(a.as_ref() + b.as_ref())
+    }
+
+    ///
+    /// self - other, see `add_aligned`
+    ///
+    pub fn sub_aligned(&self, other: &Self, alignment: ScaleAlignment) -> Result<Self, Error> {
+        let (a, b) = self.align_scale(other, alignment)?;
+        Ok(a.as_ref() - b.as_ref())
+    }
+
+    ///
+    /// Reduce a ciphertext of any dimension k + 1 back into 2 dimensions, where keys[i] switches
+    /// from s^(i + 2), i.e. the keys for s^2, ..., s^k of `Rwle::relin_keys`.
+    ///
+    /// Sums of products can be relinearized once, after summing, instead of after every product.
+    ///
+    pub fn relinearize(&self, keys: &[KeySwitchKey]) -> CipherText<'n, BigInt, 2> {
+        assert!(
+            keys.len() + 2 >= self.dim(),
+            "relinearize: need a key for every power of s up to s^{}",
+            self.dim() - 1
+        );
+        let modulus = &self.modulus;
+
+        let mut c0 = self.c[0].clone();
+        let mut c1 = self.c[1].clone();
+        for (c, key) in self.c[2..].iter().zip(keys) {
+            let (ks0, ks1) = key_switch_at(c, key, modulus);
+            c0 += ks0;
+            c1 += ks1;
+        }
+
+        CipherText {
+            c: [c0 % modulus, c1 % modulus].into(),
+            modulus: modulus.clone(),
+            scaling_factor: self.scaling_factor.clone(),
+            num_slots: self.num_slots,
+        }
+    }
+}
+
+///
+/// How `CipherText::align_scale` brings two ciphertexts to the same scaling factor
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleAlignment {
+    /// Multiply the side with the smaller scale by the ratio, which keeps the modulus
+    Multiply,
+    /// Rescale the side with the larger scale by the ratio, which keeps the scale small
+    Rescale,
 }
 
 impl<'n> CipherText<'n, BigInt, 3> {
     ///
     /// This takes a 3-dimensional ciphertext and reduces it back into 2-dimensions
     ///
-    pub fn relin(
-        &self,
-        relin_key: &'n PublicKey<BigInt>,
-        big_modulus: &'n BigInt,
-    ) -> CipherText<BigInt, 2> {
+    pub fn relin(&self, relin_key: &KeySwitchKey) -> CipherText<'n, BigInt, 2> {
+        self.relinearize(std::slice::from_ref(relin_key))
+    }
+}
+
+impl<'n> CipherText<'n, BigInt, 2> {
+    ///
+    /// Rotate the slots of the ciphertext to the left by `rotation`
+    ///
+    /// The rotation is taken modulo the number of slots, and the key must have been created with
+    /// `Rwle::rotation_key` for that reduced rotation.
+    ///
+    pub fn rotate(&self, rotation: usize, rot_key: &KeySwitchKey) -> Self {
+        let poly_degree = self.c[0].poly_degree;
+        let galois_elt = galois_element(rotation % self.num_slots, poly_degree);
+        self.apply_galois(galois_elt, rot_key)
+    }
+
+    ///
+    /// Take the complex conjugate of every slot of the ciphertext
+    ///
+    /// The key must have been created with `Rwle::conjugation_key`.
+    ///
+    pub fn conjugate(&self, conj_key: &KeySwitchKey) -> Self {
+        let poly_degree = self.c[0].poly_degree;
+        self.apply_galois(2 * poly_degree - 1, conj_key)
+    }
+
+    ///
+    /// Apply X -> X^k to both components, then switch the result from s(X^k) back to s(X)
+    ///
+    fn apply_galois(&self, galois_elt: usize, key: &KeySwitchKey) -> Self {
         let modulus = &self.modulus;
 
-        let mut new_c0 = (&relin_key.0 * &self.c[2]) % &(modulus * big_modulus);
-        new_c0.coef = new_c0.coef.iter().map(|x| x / big_modulus).collect();
-        new_c0 = (new_c0 + &self.c[0]) % modulus;
+        let c0 = self.c[0].automorphism(galois_elt);
+        let c1 = self.c[1].automorphism(galois_elt);
 
-        let mut new_c1 = (&relin_key.1 * &self.c[2]) % &(modulus * big_modulus);
-        new_c1.coef = new_c1.coef.iter().map(|x| x / big_modulus).collect();
-        new_c1 = (new_c1 + &self.c[1]) % modulus;
+        let (ks0, ks1) = key_switch_at(&c1, key, modulus);
 
         CipherText {
-            c: [new_c0, new_c1].into(),
+            c: [(&c0 + &ks0) % modulus, ks1 % modulus].into(),
             modulus: modulus.clone(),
             scaling_factor: self.scaling_factor.clone(),
+            num_slots: self.num_slots,
         }
     }
 }
 
 #[derive(Debug)]
-pub struct PlainText<'n, T> {
+pub struct PlainText<'n, T: Coefficient> {
     pub poly: PolynomialRing<'n, T>,
     scaling_factor: BigUint,
+    num_slots: usize,
+}
+
+impl<T: Coefficient> PlainText<'_, T> {
+    ///
+    /// Number of slots that hold values, N/2 unless the values are sparsely packed
+    ///
+    pub fn num_slots(&self) -> usize {
+        self.num_slots
+    }
 }
 
 type PrivateKey<'n, T> = PolynomialRing<'n, T>;
 
+// Size of the primes of the key switching moduli Q and P
+const KEY_PRIME_BITS: u32 = 60;
+
+// NTT values of a polynomial, one vector per prime of a basis
+type Evals = Vec<Vec<u64>>;
+
+///
+/// Key used to switch a ciphertext component from one secret key s' to the secret key s.
+///
+/// This is hybrid key switching in RNS form. The key has its own modulus Q, a product of NTT
+/// primes: the primes of the Chinese Remainder Theorem context of the secret key when they make
+/// up the ciphertext modulus, and new primes whose product is larger than it otherwise. The
+/// primes of Q are split into `dnum` groups Q_j, a component c is split into the digits
+/// [c]_{Q_j}, and the j-th digit is multiplied by an encryption of P * s' on the primes of Q_j
+/// and of 0 on the others, modulo P * Q. The special modulus P is a product of NTT primes as
+/// large as a group, so dividing by it afterwards leaves a small error.
+///
+/// The keys are kept in evaluation form modulo every prime of Q * P, so switching only takes
+/// NTTs and word-sized arithmetic, apart from bringing the component from its modulus to Q and
+/// back.
+///
+#[derive(Debug)]
+pub struct KeySwitchKey {
+    // (b_j, a_j) with b_j = -a_j * s + e_j, plus P * s' on the primes of Q_j, for every digit j
+    keys: Vec<(Evals, Evals)>,
+    basis: KeySwitchBasis,
+    modulus: BigInt,
+}
+
+impl KeySwitchKey {
+    pub fn dnum(&self) -> usize {
+        self.keys.len()
+    }
+
+    ///
+    /// The ciphertext modulus the key was made for, which `key_switch` assumes
+    ///
+    pub fn modulus(&self) -> &BigInt {
+        &self.modulus
+    }
+
+    ///
+    /// Primes of Q, where every digit is a group of consecutive primes
+    ///
+    pub fn primes(&self) -> &[u64] {
+        self.basis.q.primes()
+    }
+
+    ///
+    /// Primes of the special modulus P
+    ///
+    pub fn special_primes(&self) -> &[u64] {
+        &self.basis.qp.primes()[self.basis.q.primes().len()..]
+    }
+}
+
+///
+/// The moduli Q and P of a `KeySwitchKey`, with the conversions between their primes
+///
+#[derive(Debug)]
+struct KeySwitchBasis {
+    q: Crt,
+    // The primes of Q followed by the ones of P
+    qp: Crt,
+    // ModDown from Q * P to Q
+    mod_down: ExtendedBasis,
+    // For every digit, its primes in Q and the ModUp from them to all the primes of Q * P
+    digits: Vec<(Range<usize>, ExtendedBasis)>,
+    // P mod q_i for every prime of Q
+    p_mod_q: Vec<u64>,
+}
+
+impl KeySwitchBasis {
+    ///
+    /// Q from the primes of `crt` when they make up `modulus` and there are at least `dnum` of
+    /// them, and from new primes whose product is larger than `modulus` otherwise
+    ///
+    fn new(modulus: &BigInt, crt: Option<&Crt>, poly_degree: usize, dnum: usize) -> Self {
+        let degree = poly_degree as u64;
+
+        let q_primes = match crt {
+            Some(crt) if crt.modulus == *modulus && crt.primes().len() >= dnum => {
+                crt.primes().to_vec()
+            }
+            _ => {
+                let bits = modulus.bits() + 1;
+                let num_primes = (dnum as u64).max(bits.div_ceil(KEY_PRIME_BITS as u64));
+                // Far enough above 2N to leave plenty of primes = 1 (mod 2N)
+                let min_bits = poly_degree.trailing_zeros() + 12;
+                let prime_bits = (bits.div_ceil(num_primes) as u32).max(min_bits);
+                ntt_primes(&vec![prime_bits; num_primes as usize], degree)
+            }
+        };
+
+        let num_q = q_primes.len();
+        let ranges: Vec<Range<usize>> = (0..dnum)
+            .map(|j| j * num_q / dnum..(j + 1) * num_q / dnum)
+            .collect();
+
+        // P is at least about as large as the largest group
+        let group_bits = ranges
+            .iter()
+            .map(|range| {
+                let group = q_primes[range.clone()].iter();
+                group.fold(BigInt::one(), |acc, &p| acc * p).bits()
+            })
+            .max()
+            .unwrap();
+        let p_primes: Vec<u64> = NttPrimes::new(KEY_PRIME_BITS, degree)
+            .filter(|p| !q_primes.contains(p))
+            .take(group_bits.div_ceil(KEY_PRIME_BITS as u64) as usize)
+            .collect();
+        let qp_primes = [q_primes.as_slice(), p_primes.as_slice()].concat();
+
+        let q = Crt::from_primes(&q_primes, degree);
+        let qp = Crt::from_primes(&qp_primes, degree);
+        let mod_down = ExtendedBasis::new(&q, &Crt::from_primes(&p_primes, degree));
+
+        let digits = ranges
+            .into_iter()
+            .map(|range| {
+                let group = Crt::from_primes(&q_primes[range.clone()], degree);
+                let others: Vec<u64> = qp_primes
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| !range.contains(i))
+                    .map(|(_, &p)| p)
+                    .collect();
+                let mod_up = ExtendedBasis::new(&group, &Crt::from_primes(&others, degree));
+                (range, mod_up)
+            })
+            .collect();
+
+        let p_mod_q = q
+            .moduli()
+            .iter()
+            .map(|m| {
+                p_primes
+                    .iter()
+                    .fold(1, |acc, &p| m.mul_mod(acc, m.reduce(p)))
+            })
+            .collect();
+
+        KeySwitchBasis {
+            q,
+            qp,
+            mod_down,
+            digits,
+            p_mod_q,
+        }
+    }
+
+    ///
+    /// NTT values modulo every prime of Q * P of a polynomial in coefficient form
+    ///
+    fn evals(&self, poly: &PolynomialRing<BigInt>) -> Evals {
+        let mut coef = poly.coef.clone();
+        coef.resize(poly.poly_degree, BigInt::zero());
+        let mut evals = self.qp.decompose_poly(&coef);
+        self.forward(&mut evals);
+        evals
+    }
+
+    fn forward(&self, evals: &mut Evals) {
+        evals
+            .par_iter_mut()
+            .zip(&self.qp.ntts)
+            .for_each(|(x, ntt)| ntt.forward(x));
+    }
+
+    fn inverse(&self, evals: &mut Evals) {
+        evals
+            .par_iter_mut()
+            .zip(&self.qp.ntts)
+            .for_each(|(x, ntt)| ntt.inverse(x));
+    }
+
+    ///
+    /// The digits [c]_{Q_j} of c, given by its residues modulo Q, each extended to Q * P and in
+    /// evaluation form
+    ///
+    fn decompose(&self, residues: &[Vec<u64>]) -> Vec<Evals> {
+        self.digits
+            .iter()
+            .map(|(range, mod_up)| {
+                // The residues of the group come first, then the ones of all the other primes
+                let mut group = mod_up.mod_up(&residues[range.clone()]);
+                let mut digit = group.split_off(range.len());
+                digit.splice(range.start..range.start, group);
+                self.forward(&mut digit);
+                digit
+            })
+            .collect()
+    }
+
+    ///
+    /// sum_j digits_j * keys_j, slot by slot modulo every prime of Q * P
+    ///
+    fn inner_product(&self, digits: &[Evals], keys: &[&Evals]) -> Evals {
+        self.qp
+            .moduli()
+            .par_iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let mut sum = vec![0; digits[0][i].len()];
+                for (digit, key) in digits.iter().zip(keys) {
+                    for (x, (&d, &k)) in sum.iter_mut().zip(digit[i].iter().zip(&key[i])) {
+                        *x = m.add_mod(*x, m.mul_mod(d, k));
+                    }
+                }
+                sum
+            })
+            .collect()
+    }
+}
+
+impl<'n> PublicKey<'n, BigInt> {
+    ///
+    /// Move both parts of the key into evaluation form, so they are not transformed again on
+    /// every multiplication. Needs a Chinese Remainder Theorem context.
+    ///
+    pub fn to_ntt(self) -> Self {
+        PublicKey(self.0.to_ntt(), self.1.to_ntt())
+    }
+}
+
+///
+/// Divide by `divisor` and round to the nearest integer
+///
+fn div_round(num: &BigInt, divisor: &BigInt) -> BigInt {
+    let half: BigInt = divisor / 2;
+    if num.is_negative() {
+        let num: BigInt = half - num;
+        -(num / divisor)
+    } else {
+        (num + half) / divisor
+    }
+}
+
+///
+/// Run `op` on the thread pool of the Chinese Remainder Theorem context, if there is one
+///
+fn install<R: Send>(crt: Option<&Crt>, op: impl FnOnce() -> R + Send) -> R {
+    match crt {
+        Some(crt) => crt.install(op),
+        None => op(),
+    }
+}
+
+///
+/// Switch a ciphertext component `c`, decryptable with s', into a pair (c0, c1) such that
+/// c0 + c1 * s ~= c * s'.
+///
+pub fn key_switch<'n>(
+    c: &PolynomialRing<'n, BigInt>,
+    ksk: &KeySwitchKey,
+) -> (PolynomialRing<'n, BigInt>, PolynomialRing<'n, BigInt>) {
+    key_switch_at(c, ksk, &ksk.modulus)
+}
+
+///
+/// `key_switch` for a component modulo any q up to the modulus Q of the key, e.g. after
+/// rescaling. The component is brought to round(Q / q * c) modulo Q, switched, and brought back
+/// by the inverse factor, which is exact when q = Q and only adds a rounding error otherwise.
+///
+pub fn key_switch_at<'n>(
+    c: &PolynomialRing<'n, BigInt>,
+    ksk: &KeySwitchKey,
+    modulus: &BigInt,
+) -> (PolynomialRing<'n, BigInt>, PolynomialRing<'n, BigInt>) {
+    let basis = &ksk.basis;
+    let big_modulus = &basis.q.modulus;
+    assert!(
+        modulus <= big_modulus,
+        "key_switch: the modulus needs to be at most the modulus of the key"
+    );
+    let poly_degree = c.poly_degree;
+
+    install(c.crt(), || {
+        let mut coef: Vec<BigInt> = (c % modulus)
+            .coef
+            .par_iter()
+            .map(|x| div_round(&(x * big_modulus), modulus))
+            .collect();
+        coef.resize(poly_degree, BigInt::zero());
+        let digits = basis.decompose(&basis.q.decompose_poly(&coef));
+
+        // ModDown by P, then back from Q to the modulus of the component
+        let switch = |keys: Vec<&Evals>| {
+            let mut sum = basis.inner_product(&digits, &keys);
+            basis.inverse(&mut sum);
+            let coef = basis
+                .q
+                .reconstruct_poly(&basis.mod_down.mod_down(&sum))
+                .into_par_iter()
+                .map(|x| div_round(&(x.mod_ring(big_modulus) * modulus), big_modulus))
+                .map(|x| x.mod_ring(modulus))
+                .collect();
+            PolynomialRing::new(poly_degree, coef).add_option_crt(c.crt())
+        };
+
+        join(
+            || switch(ksk.keys.iter().map(|key| &key.0).collect()),
+            || switch(ksk.keys.iter().map(|key| &key.1).collect()),
+        )
+    })
+}
+
+///
+/// The Galois element 5^rotation (mod 2N) that rotates slots to the left by `rotation`
+///
+fn galois_element(rotation: usize, poly_degree: usize) -> usize {
+    let mut elt = 1;
+    for _ in 0..rotation {
+        elt = (elt * 5) % (2 * poly_degree);
+    }
+    elt
+}
+
+///
+/// a = a * b mod modulus, in place
+///
+fn mul_reduce<'n>(
+    a: &mut PolynomialRing<'n, BigInt>,
+    b: &PolynomialRing<'n, BigInt>,
+    modulus: &BigInt,
+) {
+    *a *= b;
+    *a %= modulus;
+}
+
+///
+/// (a0 + a1 * s + ...) * (b0 + b1 * s + ...) = sum_k (sum_{i + j = k} a_i * b_j) * s^k, with the
+/// components computed in parallel
+///
+fn tensor<'n>(
+    a: &[PolynomialRing<'n, BigInt>],
+    b: &[PolynomialRing<'n, BigInt>],
+    modulus: &BigInt,
+) -> Vec<PolynomialRing<'n, BigInt>> {
+    install(a[0].crt(), || {
+        (0..a.len() + b.len() - 1)
+            .into_par_iter()
+            .map(|k| {
+                // Every a_i * b_j with i + j = k
+                let first = k.saturating_sub(b.len() - 1);
+                let last = k.min(a.len() - 1);
+                let mut c = &a[first] * &b[k - first];
+                for i in first + 1..=last {
+                    c += &a[i] * &b[k - i];
+                }
+                c % modulus
+            })
+            .collect()
+    })
+}
+
+///
+/// Adds the components as they are, so both ciphertexts need the same scaling factor and modulus.
+/// See `CipherText::add_aligned` otherwise.
+///
 impl<'n, const N: usize> std::ops::Add for &CipherText<'n, BigInt, N> {
     type Output = CipherText<'n, BigInt, N>;
     fn add(self, other: &CipherText<'n, BigInt, N>) -> Self::Output {
@@ -77,6 +767,7 @@ impl<'n, const N: usize> std::ops::Add for &CipherText<'n, BigInt, N> {
             c,
             modulus,
             scaling_factor,
+            num_slots: self.num_slots,
         }
     }
 }
@@ -91,42 +782,172 @@ impl<'n, const N: usize> std::ops::Sub for &CipherText<'n, BigInt, N> {
             c,
             modulus,
             scaling_factor,
+            num_slots: self.num_slots,
         }
     }
 }
 
+impl<'n, const N: usize> std::ops::AddAssign<&CipherText<'n, BigInt, N>>
+    for CipherText<'n, BigInt, N>
+{
+    fn add_assign(&mut self, other: &CipherText<'n, BigInt, N>) {
+        self.c.iter_mut().zip(&other.c).for_each(|(x, y)| *x += y);
+    }
+}
+
+impl<'n, const N: usize> std::ops::AddAssign for CipherText<'n, BigInt, N> {
+    fn add_assign(&mut self, other: CipherText<'n, BigInt, N>) {
+        *self += &other;
+    }
+}
+
+impl<'n, const N: usize> std::ops::Add<&CipherText<'n, BigInt, N>> for CipherText<'n, BigInt, N> {
+    type Output = Self;
+    fn add(mut self, other: &CipherText<'n, BigInt, N>) -> Self {
+        self += other;
+        self
+    }
+}
+
+impl<'n, const N: usize> std::ops::Add for CipherText<'n, BigInt, N> {
+    type Output = Self;
+    fn add(self, other: CipherText<'n, BigInt, N>) -> Self {
+        self + &other
+    }
+}
+
+impl<'n, const N: usize> std::ops::Add<CipherText<'n, BigInt, N>> for &CipherText<'n, BigInt, N> {
+    type Output = CipherText<'n, BigInt, N>;
+    fn add(self, other: CipherText<'n, BigInt, N>) -> Self::Output {
+        // Reuse the storage of other, keeping the scaling factor of self
+        let mut res = other + self;
+        res.scaling_factor = self.scaling_factor.clone();
+        res
+    }
+}
+
+impl<'n, const N: usize> std::ops::SubAssign<&CipherText<'n, BigInt, N>>
+    for CipherText<'n, BigInt, N>
+{
+    fn sub_assign(&mut self, other: &CipherText<'n, BigInt, N>) {
+        self.c.iter_mut().zip(&other.c).for_each(|(x, y)| *x -= y);
+    }
+}
+
+impl<'n, const N: usize> std::ops::SubAssign for CipherText<'n, BigInt, N> {
+    fn sub_assign(&mut self, other: CipherText<'n, BigInt, N>) {
+        *self -= &other;
+    }
+}
+
+impl<'n, const N: usize> std::ops::Sub<&CipherText<'n, BigInt, N>> for CipherText<'n, BigInt, N> {
+    type Output = Self;
+    fn sub(mut self, other: &CipherText<'n, BigInt, N>) -> Self {
+        self -= other;
+        self
+    }
+}
+
+impl<'n, const N: usize> std::ops::Sub for CipherText<'n, BigInt, N> {
+    type Output = Self;
+    fn sub(self, other: CipherText<'n, BigInt, N>) -> Self {
+        self - &other
+    }
+}
+
+impl<'n, const N: usize> std::ops::Sub<CipherText<'n, BigInt, N>> for &CipherText<'n, BigInt, N> {
+    type Output = CipherText<'n, BigInt, N>;
+    fn sub(self, other: CipherText<'n, BigInt, N>) -> Self::Output {
+        // a - b = -(b - a), reusing the storage of b
+        let mut res = -(other - self);
+        res.scaling_factor = self.scaling_factor.clone();
+        res
+    }
+}
+
+impl<'n, const N: usize> std::ops::Neg for CipherText<'n, BigInt, N> {
+    type Output = Self;
+    fn neg(mut self) -> Self {
+        self.c.iter_mut().for_each(|x| x.neg_assign());
+        self
+    }
+}
+
+impl<'n, const N: usize> std::ops::Neg for &CipherText<'n, BigInt, N> {
+    type Output = CipherText<'n, BigInt, N>;
+    fn neg(self) -> Self::Output {
+        -self.clone()
+    }
+}
+
+///
+/// Multiply every component by a plaintext, in place. The scaling factors multiply, like for the
+/// product of two ciphertexts.
+///
+impl<'n, const N: usize> std::ops::MulAssign<&PlainText<'n, BigInt>> for CipherText<'n, BigInt, N> {
+    fn mul_assign(&mut self, plain: &PlainText<'n, BigInt>) {
+        let modulus = &self.modulus;
+        self.c
+            .iter_mut()
+            .for_each(|x| mul_reduce(x, &plain.poly, modulus));
+        self.scaling_factor *= &plain.scaling_factor;
+    }
+}
+
+impl<'n, const N: usize> std::ops::Mul<&PlainText<'n, BigInt>> for CipherText<'n, BigInt, N> {
+    type Output = Self;
+    fn mul(mut self, plain: &PlainText<'n, BigInt>) -> Self {
+        self *= plain;
+        self
+    }
+}
+
+impl<'n, const N: usize> std::ops::Mul<&PlainText<'n, BigInt>> for &CipherText<'n, BigInt, N> {
+    type Output = CipherText<'n, BigInt, N>;
+    fn mul(self, plain: &PlainText<'n, BigInt>) -> Self::Output {
+        self.clone() * plain
+    }
+}
+
 ///
 /// Multiply two ciphertexts together.
 ///
-/// This will increase the dimensionality of the ciphertext. In the current implementation it
-/// goes from dim 2 -> dim 3
+/// This will increase the dimensionality of the ciphertext, from dim 2 -> dim 3. See
+/// `CipherText::tensor` for other dimensions.
 ///
 impl<'a, 'b, 'n> std::ops::Mul<&'b CipherText<'n, BigInt, 2>> for &'a CipherText<'n, BigInt, 2> {
     type Output = CipherText<'n, BigInt, 3>;
     fn mul(self, other: &'b CipherText<'n, BigInt, 2>) -> Self::Output {
-        let modulus = self.modulus.clone();
-
-        let c0 = &self.c[0] * &other.c[0];
-        let c0 = c0 % &modulus;
+        self.tensor(other)
+    }
+}
 
-        let c1 = &self.c[0] * &other.c[1] + &other.c[0] * &self.c[1];
-        let c1 = c1 % &modulus;
+impl<'n> std::ops::Mul<&CipherText<'n, BigInt, 2>> for CipherText<'n, BigInt, 2> {
+    type Output = CipherText<'n, BigInt, 3>;
+    fn mul(self, other: &CipherText<'n, BigInt, 2>) -> Self::Output {
+        &self * other
+    }
+}
 
-        let c2 = &self.c[1] * &other.c[1];
-        let c2 = c2 % &modulus;
+impl<'n> std::ops::Mul<CipherText<'n, BigInt, 2>> for &CipherText<'n, BigInt, 2> {
+    type Output = CipherText<'n, BigInt, 3>;
+    fn mul(self, other: CipherText<'n, BigInt, 2>) -> Self::Output {
+        self * &other
+    }
+}
 
-        CipherText {
-            c: [c0, c1, c2].into(),
-            modulus,
-            scaling_factor: &self.scaling_factor * &other.scaling_factor,
-        }
+impl<'n> std::ops::Mul for CipherText<'n, BigInt, 2> {
+    type Output = CipherText<'n, BigInt, 3>;
+    fn mul(self, other: CipherText<'n, BigInt, 2>) -> Self::Output {
+        &self * &other
     }
 }
 
 #[derive(Debug)]
-pub struct Rwle<'n, T> {
+pub struct Rwle<'n, T: Coefficient> {
     sk: PrivateKey<'n, T>,
     pk: PublicKey<'n, T>,
+    modulus: BigInt,
 }
 
 impl<'a> Rwle<'a, BigInt> {
@@ -143,57 +964,106 @@ impl<'a> Rwle<'a, BigInt> {
         let sk = PrivateKey::rand_binary(poly_degree, size);
 
         // First part of our public key, a
-        let mut a = PolynomialRing::rand_uniform(modulus, poly_degree, size);
-        // Flip the sign in calculation of public key
-        a.coef = a.coef.iter().map(|x| -x).collect();
+        let a = PolynomialRing::rand_uniform(modulus, poly_degree, size);
 
         // A little bit of noise
         let e = PolynomialRing::rand_normal(poly_degree, size);
 
-        // Second part of public key, b
-        let b = &a * &sk + &e;
-
-        // Return back sign
-        a.coef = a.coef.iter().map(|x| -x).collect();
+        // Second part of public key, b = -a * s + e
+        let b = -(&a * &sk) + &e;
 
         Rwle {
             sk,
             pk: PublicKey(b, a),
+            modulus: modulus.clone(),
         }
     }
 
-    pub fn switch_key(
-        &self,
-        big_modulo: &BigInt,
-        new_key: &PolynomialRing<BigInt>,
-    ) -> PublicKey<BigInt> {
-        let mod_squared = big_modulo * big_modulo;
-
-        let swk = PolynomialRing::rand_uniform(&mod_squared, self.sk.poly_degree, self.sk.len());
-        let swk_e = PolynomialRing::rand_normal(self.sk.poly_degree, self.sk.len());
-
-        let mut sw0 = (&swk * &self.sk) % &mod_squared;
-
-        sw0.coef = sw0.coef.iter().map(|x| -x).collect();
-        sw0 = (&sw0 + &swk_e) % &mod_squared;
-
-        let temp = PolynomialRing::new(
-            self.sk.poly_degree,
-            new_key
-                .coef
-                .iter()
-                .map(|x| (x * big_modulo).mod_ring(&mod_squared))
-                .collect(),
-        );
+    ///
+    /// Create a key that switches ciphertext components decryptable by `new_key` to ones
+    /// decryptable by our secret key, using `dnum` digits. See `KeySwitchKey`.
+    ///
+    pub fn switch_key(&self, new_key: &PolynomialRing<'a, BigInt>, dnum: usize) -> KeySwitchKey {
+        assert!(dnum > 0, "switch_key: dnum needs to be at least 1");
+
+        let poly_degree = self.sk.poly_degree;
+        let size = self.sk.len();
+        let basis = KeySwitchBasis::new(&self.modulus, self.sk.crt(), poly_degree, dnum);
+        let qp = &basis.qp;
+
+        let sk = basis.evals(&self.sk);
+        let new_key = basis.evals(new_key);
 
-        sw0 = (&sw0 + &temp) % &mod_squared;
+        let keys = basis
+            .digits
+            .iter()
+            .map(|(range, _)| {
+                let a = basis.evals(&PolynomialRing::rand_uniform(
+                    &qp.modulus,
+                    poly_degree,
+                    size,
+                ));
+                let mut b = basis.evals(&PolynomialRing::rand_normal(poly_degree, size));
+
+                for (i, m) in qp.moduli().iter().enumerate() {
+                    let gadget = if range.contains(&i) {
+                        basis.p_mod_q[i]
+                    } else {
+                        0
+                    };
+                    for (x, ((&a, &s), &s_new)) in b[i]
+                        .iter_mut()
+                        .zip(a[i].iter().zip(&sk[i]).zip(&new_key[i]))
+                    {
+                        *x = m.sub_mod(*x, m.mul_mod(a, s));
+                        *x = m.add_mod(*x, m.mul_mod(gadget, s_new));
+                    }
+                }
+                (b, a)
+            })
+            .collect();
+
+        KeySwitchKey {
+            keys,
+            basis,
+            modulus: self.modulus.clone(),
+        }
+    }
+
+    pub fn relin_key(&self, dnum: usize) -> KeySwitchKey {
+        let sk_squared = (&self.sk * &self.sk) % &self.modulus;
+        self.switch_key(&sk_squared, dnum)
+    }
+
+    ///
+    /// Keys for s^2, ..., s^max_power, needed by `CipherText::relinearize` for ciphertexts of up
+    /// to max_power + 1 components
+    ///
+    pub fn relin_keys(&self, max_power: usize, dnum: usize) -> Vec<KeySwitchKey> {
+        let mut sk_pow = self.sk.clone();
+        (2..=max_power)
+            .map(|_| {
+                sk_pow = (&sk_pow * &self.sk) % &self.modulus;
+                self.switch_key(&sk_pow, dnum)
+            })
+            .collect()
+    }
 
-        PublicKey(sw0, swk)
+    ///
+    /// Key needed by `CipherText::rotate` to rotate slots to the left by `rotation`
+    ///
+    pub fn rotation_key(&self, rotation: usize, dnum: usize) -> KeySwitchKey {
+        let galois_elt = galois_element(rotation, self.sk.poly_degree);
+        let sk_rotated = self.sk.automorphism(galois_elt);
+        self.switch_key(&sk_rotated, dnum)
     }
 
-    pub fn relin_key(&self, big_modulo: &BigInt) -> PublicKey<BigInt> {
-        let sk_squared = (&self.sk * &self.sk) % big_modulo;
-        self.switch_key(big_modulo, &sk_squared)
+    ///
+    /// Key needed by `CipherText::conjugate`
+    ///
+    pub fn conjugation_key(&self, dnum: usize) -> KeySwitchKey {
+        let sk_conjugated = self.sk.automorphism(2 * self.sk.poly_degree - 1);
+        self.switch_key(&sk_conjugated, dnum)
     }
 
     pub fn public(&self) -> &PublicKey<BigInt> {
@@ -203,6 +1073,13 @@ impl<'a> Rwle<'a, BigInt> {
     pub fn private(&self) -> &PrivateKey<BigInt> {
         &self.sk
     }
+
+    ///
+    /// A `Decryptor` for ciphertexts of up to max_power + 1 components
+    ///
+    pub fn decryptor(&self, max_power: usize) -> Decryptor<'a> {
+        Decryptor::new(&self.sk, max_power)
+    }
 }
 
 pub fn encrypt<'n>(
@@ -227,77 +1104,266 @@ pub fn encrypt<'n>(
         c: [c0, c1].into(),
         modulus: modulus.clone(),
         scaling_factor: plain.scaling_factor.clone(),
+        num_slots: plain.num_slots,
     }
 }
 
+///
+/// Decrypt a ciphertext of any dimension, leaving it as it is. To decrypt many ciphertexts with
+/// the same key, a `Decryptor` computes the powers of the key only once.
+///
 pub fn decrypt<'n, const N: usize>(
-    sk: &'n PolynomialRing<BigInt>,
-    ct: CipherText<'n, BigInt, N>,
+    sk: &PolynomialRing<'n, BigInt>,
+    ct: &CipherText<'n, BigInt, N>,
 ) -> PlainText<'n, BigInt> {
-    let modulus = ct.modulus;
+    Decryptor::new(sk, ct.dim() - 1).decrypt(ct)
+}
 
-    let mut poly = ct.c[0].clone();
-    let mut sk_pow = sk.clone();
+///
+/// Decrypts with a secret key s, keeping the powers s, s^2, ..., s^k that ciphertexts of up to
+/// k + 1 components need.
+///
+/// The powers are exact over the integers rather than reduced, so they hold for any ciphertext
+/// modulus. Their coefficients grow like N^(k - 1), which stays small for the dimensions that
+/// relinearization keeps ciphertexts at.
+///
+#[derive(Debug, Clone)]
+pub struct Decryptor<'n> {
+    // s^(i + 1) at index i
+    sk_powers: Vec<PolynomialRing<'n, BigInt>>,
+}
 
-    for i in 1..N {
-        poly = (poly + &sk_pow * &ct.c[i]) % &modulus;
-        // TODO: This does one extra computation at last element. Fix this.
-        sk_pow = (&sk_pow * sk) % &modulus;
+impl<'n> Decryptor<'n> {
+    pub fn new(sk: &PolynomialRing<'n, BigInt>, max_power: usize) -> Self {
+        let mut sk_powers = Vec::with_capacity(max_power);
+        if max_power > 0 {
+            sk_powers.push(sk.clone());
+        }
+        for i in 1..max_power {
+            let next = &sk_powers[i - 1] * sk;
+            sk_powers.push(next);
+        }
+        Self { sk_powers }
     }
 
-    let poly = poly % &modulus;
+    ///
+    /// Highest power of the key, so ciphertexts of up to max_power + 1 components can be
+    /// decrypted
+    ///
+    pub fn max_power(&self) -> usize {
+        self.sk_powers.len()
+    }
+
+    ///
+    /// c0 + c1 * s + c2 * s^2 + ... modulo the modulus of the ciphertext
+    ///
+    pub fn decrypt<const N: usize>(&self, ct: &CipherText<'n, BigInt, N>) -> PlainText<'n, BigInt> {
+        assert!(
+            ct.dim() <= self.max_power() + 1,
+            "decrypt: a ciphertext of {} components needs the key up to s^{}",
+            ct.dim(),
+            ct.dim() - 1
+        );
+        let modulus = &ct.modulus;
 
-    PlainText {
-        poly,
-        scaling_factor: ct.scaling_factor,
+        let mut poly = ct.c[0].clone();
+        for (c, sk_pow) in ct.c.iter().skip(1).zip(&self.sk_powers) {
+            poly += sk_pow * c;
+        }
+
+        PlainText {
+            poly: poly % modulus,
+            scaling_factor: ct.scaling_factor.clone(),
+            num_slots: ct.num_slots,
+        }
+    }
+
+    ///
+    /// Decrypt, and measure the noise as the difference to the plaintext that the ciphertext is
+    /// expected to hold, centered modulo the ciphertext modulus. Returns the noise polynomial and
+    /// the bit size of its largest coefficient.
+    ///
+    pub fn decrypt_with_noise<const N: usize>(
+        &self,
+        ct: &CipherText<'n, BigInt, N>,
+        expected: &PlainText<'n, BigInt>,
+    ) -> (PolynomialRing<'n, BigInt>, u64) {
+        let plain = self.decrypt(ct);
+        let noise = (plain.poly - &expected.poly) % &ct.modulus;
+        let bits = noise.bit_size();
+        (noise, bits)
     }
 }
 
+///
+/// Encode real values into a plaintext of degree N, see `encode_real`.
+///
+/// Panics if there are more than N/2 values.
+///
 pub fn encode<'n>(
     message: &[f64],
     scaling_factor: usize,
     encoder: &CKKSEncoder,
 ) -> PlainText<'n, BigInt> {
-    let num_values = message.len();
-    let plain_len = num_values << 1;
+    encode_real(message, scaling_factor, encoder).expect("encode: message too long")
+}
+
+///
+/// Encode real values into a plaintext of degree N, see `encode_complex`.
+///
+pub fn encode_real<'n>(
+    message: &[f64],
+    scaling_factor: usize,
+    encoder: &CKKSEncoder,
+) -> Result<PlainText<'n, BigInt>, Error> {
+    let message: Vec<Complex64> = message.iter().map(|&x| Complex64::new(x, 0.)).collect();
+    encode_complex(&message, scaling_factor, encoder)
+}
+
+///
+/// Encode complex values into a plaintext of degree N, where the encoder was created for N.
+///
+/// Messages with fewer than N/2 values are padded with zeros, while messages with more than N/2
+/// values are rejected.
+///
+pub fn encode_complex<'n>(
+    message: &[Complex64],
+    scaling_factor: usize,
+    encoder: &CKKSEncoder,
+) -> Result<PlainText<'n, BigInt>, Error> {
+    encode_sparse(message, encoder.num_slots(), scaling_factor, encoder)
+}
 
-    let message = message.iter().map(|&x| Complex64::new(x, 0.)).collect();
+///
+/// Encode complex values into `num_slots` slots of a plaintext of degree N.
+///
+/// With fewer than N/2 slots the values are packed sparsely: they are embedded in the subring
+/// of polynomials in X^(N/2n), so only every (N/2n)-th coefficient is used. `num_slots` needs to
+/// be a power of two no larger than N/2, and shorter messages are padded with zeros.
+///
+pub fn encode_sparse<'n>(
+    message: &[Complex64],
+    num_slots: usize,
+    scaling_factor: usize,
+    encoder: &CKKSEncoder,
+) -> Result<PlainText<'n, BigInt>, Error> {
+    let gap = encoder.slot_gap(num_slots)?;
+
+    if message.len() > num_slots {
+        return Err(Error::TooManyValues {
+            len: message.len(),
+            num_slots,
+        });
+    }
+
+    let mut message = message.to_vec();
+    message.resize(num_slots, Complex64::zero());
 
     let to_scale = encoder.embedding_inv(&message);
 
-    let mut coef = vec![Zero::zero(); plain_len];
+    let scale = |x: f64| (x * scaling_factor as f64).round().to_bigint().unwrap();
 
-    for i in 0..num_values {
-        coef[i] = (to_scale[i].re * scaling_factor as f64 + 0.5)
-            .to_bigint()
-            .unwrap();
-        coef[i + num_values] = (to_scale[i].im * scaling_factor as f64 + 0.5)
-            .to_bigint()
-            .unwrap();
+    let poly_degree = encoder.poly_degree();
+    let half_degree = poly_degree >> 1;
+    let mut coef = vec![Zero::zero(); poly_degree];
+
+    for (i, x) in to_scale.iter().enumerate() {
+        coef[i * gap] = scale(x.re);
+        coef[i * gap + half_degree] = scale(x.im);
     }
 
-    PlainText {
-        poly: PolynomialRing::new(plain_len, coef),
+    Ok(PlainText {
+        poly: PolynomialRing::new(poly_degree, coef),
         scaling_factor: scaling_factor.to_biguint().unwrap(),
-    }
+        num_slots,
+    })
 }
 
+///
+/// Decode all the slots of a plaintext, see `decode_sparse`.
+///
 pub fn decode(plain: PlainText<BigInt>, encoder: &CKKSEncoder) -> Vec<Complex64> {
+    let num_slots = plain.num_slots;
+    decode_sparse(plain, num_slots, encoder).expect("decode: invalid number of slots")
+}
+
+///
+/// Decode `num_slots` values out of a plaintext encoded with the same number of slots, which is
+/// an error otherwise
+///
+pub fn decode_sparse(
+    plain: PlainText<BigInt>,
+    num_slots: usize,
+    encoder: &CKKSEncoder,
+) -> Result<Vec<Complex64>, Error> {
+    let gap = encoder.slot_gap(num_slots)?;
+    if num_slots != plain.num_slots {
+        return Err(Error::SlotCountMismatch {
+            expected: num_slots,
+            found: plain.num_slots,
+        });
+    }
+
     let scaling_factor = plain.scaling_factor.to_bigint().unwrap();
-    let plain_len = plain.poly.len();
-    let num_values = plain_len >> 1;
+    let half_degree = encoder.poly_degree() >> 1;
 
-    let mut coef = vec![Complex64::zero(); num_values];
+    // Coefficients past the end of the polynomial are zero
+    let coef_at = |i: usize| {
+        let c = plain.poly.coef.get(i).cloned().unwrap_or_else(BigInt::zero);
+        Ratio::new(c, scaling_factor.clone()).to_f64().unwrap()
+    };
 
-    for i in 0..num_values {
-        let r1 = Ratio::new(plain.poly.coef[i].clone(), scaling_factor.clone());
-        let r2 = Ratio::new(
-            plain.poly.coef[i + num_values].clone(),
-            scaling_factor.clone(),
-        );
+    let coef: Vec<Complex64> = (0..num_slots)
+        .map(|i| Complex64::new(coef_at(i * gap), coef_at(i * gap + half_degree)))
+        .collect();
+
+    Ok(encoder.embedding(&coef))
+}
 
-        coef[i] = Complex64::new(r1.to_f64().unwrap(), r2.to_f64().unwrap());
+///
+/// Encode real values directly as the coefficients of a plaintext of degree N, instead of into
+/// slots of the canonical embedding.
+///
+/// Multiplying two such plaintexts is then a negacyclic convolution of the values. Messages with
+/// fewer than N values are padded with zeros, while messages with more than N values are rejected.
+///
+pub fn encode_coeffs<'n>(
+    message: &[f64],
+    scaling_factor: usize,
+    encoder: &CKKSEncoder,
+) -> Result<PlainText<'n, BigInt>, Error> {
+    let poly_degree = encoder.poly_degree();
+    if message.len() > poly_degree {
+        return Err(Error::TooManyCoefficients {
+            len: message.len(),
+            poly_degree,
+        });
     }
 
-    encoder.embedding(&coef)
+    let mut coef: Vec<BigInt> = message
+        .iter()
+        .map(|x| (x * scaling_factor as f64).round().to_bigint().unwrap())
+        .collect();
+    coef.resize(poly_degree, Zero::zero());
+
+    Ok(PlainText {
+        poly: PolynomialRing::new(poly_degree, coef),
+        scaling_factor: scaling_factor.to_biguint().unwrap(),
+        num_slots: encoder.num_slots(),
+    })
+}
+
+///
+/// Decode the N coefficients of a plaintext encoded with `encode_coeffs`
+///
+pub fn decode_coeffs(plain: PlainText<BigInt>) -> Vec<f64> {
+    let scaling_factor = plain.scaling_factor.to_bigint().unwrap();
+
+    // Coefficients past the end of the polynomial are zero
+    (0..plain.poly.poly_degree)
+        .map(|i| {
+            let c = plain.poly.coef.get(i).cloned().unwrap_or_else(BigInt::zero);
+            Ratio::new(c, scaling_factor.clone()).to_f64().unwrap()
+        })
+        .collect()
 }
@@ -0,0 +1,54 @@
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_traits::ToPrimitive;
+
+use polyr::PolynomialRing;
+
+use crate::encoder::CKKSEncoder;
+
+///
+/// Norms in the canonical embedding, where a polynomial is mapped to its evaluations at the
+/// primitive 2N-th roots of unity. This is the norm that the error of decoded values is bounded
+/// by, so it is the one to use when comparing the noise of a ciphertext to its theoretical bound.
+///
+/// Lives here rather than in `polyr` because the embedding is computed with the `CKKSEncoder`.
+///
+pub trait CanonicalNorm {
+    ///
+    /// Evaluations at the N/2 roots zeta^(5^j), one from every pair of conjugate roots. The
+    /// evaluations at the other N/2 roots are the complex conjugates of these.
+    ///
+    fn canonical_embedding(&self, encoder: &CKKSEncoder) -> Vec<Complex64>;
+
+    ///
+    /// Largest absolute value of the canonical embedding
+    ///
+    fn canonical_inf_norm(&self, encoder: &CKKSEncoder) -> f64 {
+        self.canonical_embedding(encoder)
+            .iter()
+            .map(|x| x.norm())
+            .fold(0., f64::max)
+    }
+}
+
+impl CanonicalNorm for PolynomialRing<'_, BigInt> {
+    fn canonical_embedding(&self, encoder: &CKKSEncoder) -> Vec<Complex64> {
+        if self.is_ntt() {
+            return self.clone().from_ntt().canonical_embedding(encoder);
+        }
+        let poly_degree = encoder.poly_degree();
+        assert_eq!(
+            self.poly_degree, poly_degree,
+            "canonical_embedding: encoder was created for a different degree"
+        );
+
+        // Since zeta^(5^j * N/2) = i, p(zeta^(5^j)) is the evaluation of the polynomial of degree
+        // N/2 with coefficients c_k + i * c_(k + N/2)
+        let half_degree = poly_degree >> 1;
+        let coef_at = |k: usize| self.coef.get(k).map_or(0., |c| c.to_f64().unwrap());
+        let coef = (0..half_degree)
+            .map(|k| Complex64::new(coef_at(k), coef_at(k + half_degree)))
+            .collect();
+        encoder.embedding(&coef)
+    }
+}
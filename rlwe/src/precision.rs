@@ -0,0 +1,142 @@
+use num_complex::Complex64;
+
+///
+/// Bits of precision reported for an error of zero, the number of bits in an f64 mantissa
+///
+pub const MAX_PRECISION: f64 = f64::MANTISSA_DIGITS as f64;
+
+///
+/// Precision in bits of a set of errors, i.e. -log2(|error|)
+///
+/// `min` comes from the largest error and `max` from the smallest one, while `avg` and `median`
+/// are taken over the errors before converting them to bits.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub min: f64,
+    pub avg: f64,
+    pub median: f64,
+    pub max: f64,
+}
+
+///
+/// Precision of decoded values compared to the expected ones
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrecisionStats {
+    pub real: Stats,
+    pub imag: Stats,
+    /// Largest absolute imaginary part, when every expected value was real
+    pub imag_leakage: Option<f64>,
+}
+
+fn to_bits(err: f64) -> f64 {
+    if err == 0. {
+        MAX_PRECISION
+    } else {
+        (-err.log2()).min(MAX_PRECISION)
+    }
+}
+
+impl Stats {
+    ///
+    /// Compute the statistics of a set of absolute errors
+    ///
+    pub fn from_errors(errors: &[f64]) -> Self {
+        assert!(!errors.is_empty(), "Stats: no errors to compute from");
+
+        let mut sorted = errors.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).expect("Stats: error is NaN"));
+
+        let len = sorted.len();
+        let mid = len / 2;
+        let median = if len & 1 == 1 {
+            sorted[mid]
+        } else {
+            (sorted[mid - 1] + sorted[mid]) / 2.
+        };
+        let avg = sorted.iter().sum::<f64>() / len as f64;
+
+        Stats {
+            min: to_bits(sorted[len - 1]),
+            avg: to_bits(avg),
+            median: to_bits(median),
+            max: to_bits(sorted[0]),
+        }
+    }
+}
+
+impl PrecisionStats {
+    ///
+    /// Compare decoded values against the expected ones, slot by slot
+    ///
+    pub fn new(expected: &[Complex64], decoded: &[Complex64]) -> Self {
+        assert!(
+            decoded.len() >= expected.len(),
+            "PrecisionStats: fewer decoded values than expected values"
+        );
+
+        let decoded = &decoded[..expected.len()];
+
+        let real: Vec<f64> = expected
+            .iter()
+            .zip(decoded)
+            .map(|(x, y)| (x.re - y.re).abs())
+            .collect();
+        let imag: Vec<f64> = expected
+            .iter()
+            .zip(decoded)
+            .map(|(x, y)| (x.im - y.im).abs())
+            .collect();
+
+        let imag_leakage = if expected.iter().all(|x| x.im == 0.) {
+            Some(imag.iter().cloned().fold(0., f64::max))
+        } else {
+            None
+        };
+
+        PrecisionStats {
+            real: Stats::from_errors(&real),
+            imag: Stats::from_errors(&imag),
+            imag_leakage,
+        }
+    }
+
+    ///
+    /// Compare decoded values against expected real values
+    ///
+    pub fn new_real(expected: &[f64], decoded: &[Complex64]) -> Self {
+        let expected: Vec<Complex64> = expected.iter().map(|&x| Complex64::new(x, 0.)).collect();
+        Self::new(&expected, decoded)
+    }
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "min: {:.2}, avg: {:.2}, median: {:.2}, max: {:.2}",
+            self.min, self.avg, self.median, self.max
+        )
+    }
+}
+
+impl std::fmt::Display for PrecisionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "real bits -- {}", self.real)?;
+        write!(f, "imag bits -- {}", self.imag)?;
+        if let Some(leakage) = self.imag_leakage {
+            write!(f, "\nimag leakage -- {:e}", leakage)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn stats_test() {
+    let stats = Stats::from_errors(&[0.25, 0.5, 0.125, 0.]);
+    assert_eq!(stats.min, 1.);
+    assert_eq!(stats.max, MAX_PRECISION);
+    assert_eq!(stats.median, 3. - (1.5f64).log2());
+    assert_eq!(stats.avg, 3. - (1.75f64).log2());
+}
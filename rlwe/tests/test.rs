@@ -17,6 +17,107 @@ fn encoder() {
     println!("{:?}", plainx);
 }
 
+#[test]
+fn encode_complex_values() {
+    use num_complex::Complex64;
+
+    let n = 16;
+    let encoder = encoder::CKKSEncoder::new(n * 2);
+
+    let x = [
+        Complex64::new(0.5, -0.25),
+        Complex64::new(0.3, 0.1),
+        Complex64::new(-0.78, 0.),
+    ];
+    let plain = encode_complex(&x, 1usize << 30, &encoder).unwrap();
+    assert_eq!(plain.poly.poly_degree, n);
+    assert_eq!(plain.poly.len(), n);
+
+    let z = decode(plain, &encoder);
+    assert_eq!(z.len(), n / 2);
+
+    for (i, y) in z.iter().enumerate() {
+        let expected = x.get(i).cloned().unwrap_or_default();
+        assert_relative_eq!(expected.re, y.re, epsilon = 1e-6);
+        assert_relative_eq!(expected.im, y.im, epsilon = 1e-6);
+    }
+}
+
+#[test]
+fn encode_real_too_long() {
+    let n = 8;
+    let encoder = encoder::CKKSEncoder::new(n * 2);
+
+    let plain = encode_real(&[0.1, 0.2], 1usize << 30, &encoder).unwrap();
+    assert_eq!(plain.poly.poly_degree, n);
+
+    let err = encode_real(&[0.1; 5], 1usize << 30, &encoder).unwrap_err();
+    assert_eq!(
+        err,
+        Error::TooManyValues {
+            len: 5,
+            num_slots: 4
+        }
+    );
+}
+
+#[test]
+fn encode_sparse_values() {
+    use num_complex::Complex64;
+    use num_traits::Zero;
+
+    let n = 32;
+    let encoder = encoder::CKKSEncoder::new(n * 2);
+
+    let x: Vec<Complex64> = [0.5, -0.3, 0.78, 0.1]
+        .iter()
+        .map(|&v| Complex64::new(v, v / 2.))
+        .collect();
+
+    let plain = encode_sparse(&x, 4, 1usize << 30, &encoder).unwrap();
+    assert_eq!(plain.num_slots(), 4);
+    assert_eq!(plain.poly.len(), n);
+
+    // Only every (N/2n)-th coefficient is used
+    for (i, c) in plain.poly.coef.iter().enumerate() {
+        if i % 4 != 0 {
+            assert!(c.is_zero());
+        }
+    }
+
+    let z = decode(plain, &encoder);
+    assert_eq!(z.len(), 4);
+    for (expected, y) in x.iter().zip(z) {
+        assert_relative_eq!(expected.re, y.re, epsilon = 1e-6);
+        assert_relative_eq!(expected.im, y.im, epsilon = 1e-6);
+    }
+
+    assert_eq!(
+        encode_sparse(&x, 3, 1usize << 30, &encoder).unwrap_err(),
+        Error::InvalidSlotCount {
+            num_slots: 3,
+            max_slots: 16
+        }
+    );
+    assert_eq!(
+        encode_sparse(&x, 2, 1usize << 30, &encoder).unwrap_err(),
+        Error::TooManyValues {
+            len: 4,
+            num_slots: 2
+        }
+    );
+
+    // The number of slots to decode needs to match the encoding
+    let plain = encode_sparse(&x, 4, 1usize << 30, &encoder).unwrap();
+    assert_eq!(
+        decode_sparse(plain, 16, &encoder).unwrap_err(),
+        Error::SlotCountMismatch {
+            expected: 16,
+            found: 4
+        }
+    );
+}
+
 #[test]
 fn encrypt_decrypt() {
     let n = 8;
@@ -39,7 +140,7 @@ fn encrypt_decrypt() {
         let cipher1 = encrypt(key.public(), &q, &plain);
 
         // Decrypt our data
-        let out = decrypt(key.private(), cipher1);
+        let out = decrypt(key.private(), &cipher1);
 
         let decode = decode(out, &encoder);
 
@@ -67,7 +168,7 @@ fn add() {
 
     let cipherz = &cipherx + &ciphery;
 
-    let plainz = decrypt(key.private(), cipherz);
+    let plainz = decrypt(key.private(), &cipherz);
 
     let z = decode(plainz, &encoder);
 
@@ -78,6 +179,42 @@ fn add() {
     }
 }
 
+#[test]
+fn ciphertext_ops() {
+    let n = 8;
+    let q = 1.to_bigint().unwrap() << 600;
+
+    let key = Rwle::keygen(&q, n, n);
+    let encoder = encoder::CKKSEncoder::new(n * 2);
+
+    let x = [0.05, 0.1, 1.0, 0.005];
+    let y = [0.1, 0.02, 0.5, 0.3];
+
+    let plainx = encode(&x, 1usize << 30, &encoder);
+    let plainy = encode(&y, 1usize << 30, &encoder);
+
+    let cipherx = encrypt(key.public(), &q, &plainx);
+    let ciphery = encrypt(key.public(), &q, &plainy);
+
+    // (y - x) * y, with an in place sum and plaintext product
+    let mut cipherz = -&cipherx;
+    cipherz += &ciphery;
+    cipherz *= &plainy;
+
+    let z = decode(decrypt(key.private(), &cipherz), &encoder);
+    for ((a, b), z) in x.iter().zip(&y).zip(z) {
+        assert_relative_eq!((b - a) * b, z.re, epsilon = 1e-4)
+    }
+
+    let z = decode(
+        decrypt(key.private(), &(&cipherx - ciphery.clone())),
+        &encoder,
+    );
+    for ((a, b), z) in x.iter().zip(&y).zip(z) {
+        assert_relative_eq!(a - b, z.re, epsilon = 1e-4)
+    }
+}
+
 #[test]
 fn mul() {
     let poly_degree = 4 * 2;
@@ -97,7 +234,7 @@ fn mul() {
 
     let cipherz = &cipherx * &ciphery;
 
-    let plainz = decrypt(key.private(), cipherz);
+    let plainz = decrypt(key.private(), &cipherz);
 
     let z = decode(plainz, &encoder);
     let expected_z: Vec<f64> = x.iter().zip(&y).map(|(a, b)| a * b).collect();
@@ -111,11 +248,10 @@ fn mul() {
 fn mul_relin() {
     let poly_degree = 4 * 2;
     let ciph_modulus = 1.to_bigint().unwrap() << 600;
-    let big_modulus = 1.to_bigint().unwrap() << 1200;
     let scaling_factor = 1_usize << 30;
 
     let key = Rwle::keygen(&ciph_modulus, poly_degree as usize, poly_degree as usize);
-    let relin_key = key.relin_key(&big_modulus);
+    let relin_key = key.relin_key(3);
     let encoder = encoder::CKKSEncoder::new(poly_degree as usize * 2);
 
     let x = [0.05, 0.1, 1.0, 0.005];
@@ -127,16 +263,272 @@ fn mul_relin() {
     let ciphery = encrypt(key.public(), &ciph_modulus, &plainy);
 
     let prod = &cipherx * &ciphery;
-    let cipherz_relin = prod.relin(&relin_key, &big_modulus);
-    let plainz_relin = decrypt(key.private(), cipherz_relin);
+    let cipherz_relin = prod.relin(&relin_key);
+    let plainz_relin = decrypt(key.private(), &cipherz_relin);
     let z_relin = decode(plainz_relin, &encoder);
 
     let expected_z: Vec<f64> = x.iter().zip(&y).map(|(a, b)| a * b).collect();
+
+    let stats = precision::PrecisionStats::new_real(&expected_z, &z_relin);
+    assert!(stats.real.min > 14.);
+    assert!(stats.imag.min > 14.);
+    assert!(stats.imag_leakage.unwrap() < 1e-4);
     for (&x, y) in expected_z.iter().zip(z_relin) {
         assert_relative_eq!(x, y.re, epsilon = 1e-4)
     }
 }
 
+#[test]
+fn tensor_relinearize() {
+    let n = 8;
+    let q = 1.to_bigint().unwrap() << 600;
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&q, n, n);
+    let relin_keys = key.relin_keys(3, 3);
+    let encoder = encoder::CKKSEncoder::new(n * 2);
+
+    let x = [0.05, 0.1, 1.0, 0.005];
+    let y = [0.1, 0.02, 0.5, 0.3];
+    let w = [0.7, -0.4, 0.25, 0.9];
+    let plainx = encode(&x, scaling_factor, &encoder);
+    let plainy = encode(&y, scaling_factor, &encoder);
+    let plainw = encode(&w, scaling_factor, &encoder);
+
+    let cipherx = encrypt(key.public(), &q, &plainx);
+    let ciphery = encrypt(key.public(), &q, &plainy);
+    let cipherw = encrypt(key.public(), &q, &plainw);
+
+    // x * y * w, relinearized once from dim 4
+    let prod: CipherText<_, 4> = (&cipherx * &ciphery).tensor(&cipherw);
+    assert_eq!(prod.dim(), 4);
+    let z = decode(
+        decrypt(key.private(), &prod.relinearize(&relin_keys)),
+        &encoder,
+    );
+    for (((a, b), c), z) in x.iter().zip(&y).zip(&w).zip(z) {
+        assert_relative_eq!(a * b * c, z.re, epsilon = 1e-4)
+    }
+
+    // x * y + y * w, relinearized once after the sum
+    let sum = &(&cipherx * &ciphery) + &(&ciphery * &cipherw);
+    let z = decode(
+        decrypt(key.private(), &sum.relinearize(&relin_keys)),
+        &encoder,
+    );
+    for (((a, b), c), z) in x.iter().zip(&y).zip(&w).zip(z) {
+        assert_relative_eq!(a * b + b * c, z.re, epsilon = 1e-4)
+    }
+}
+
+#[test]
+#[should_panic]
+fn tensor_too_small() {
+    let n = 8;
+    let q = 1.to_bigint().unwrap() << 600;
+
+    let key = Rwle::keygen(&q, n, n);
+    let encoder = encoder::CKKSEncoder::new(n * 2);
+    let plain = encode(&[0.5], 1usize << 30, &encoder);
+    let cipher = encrypt(key.public(), &q, &plain);
+
+    let _: CipherText<_, 3> = (&cipher * &cipher).tensor(&cipher);
+}
+
+#[test]
+fn mul_relin_dnum() {
+    let poly_degree = 4 * 2;
+    let ciph_modulus = 1.to_bigint().unwrap() << 600;
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&ciph_modulus, poly_degree as usize, poly_degree as usize);
+    let encoder = encoder::CKKSEncoder::new(poly_degree as usize * 2);
+
+    let x = [0.05, 0.1, 1.0, 0.005];
+    let y = [0.1, 0.02, 0.5, 0.3];
+    let plainx = encode(&x, scaling_factor, &encoder);
+    let plainy = encode(&y, scaling_factor, &encoder);
+
+    let cipherx = encrypt(key.public(), &ciph_modulus, &plainx);
+    let ciphery = encrypt(key.public(), &ciph_modulus, &plainy);
+    let expected_z: Vec<f64> = x.iter().zip(&y).map(|(a, b)| a * b).collect();
+
+    for dnum in [1, 2, 5, 12] {
+        let relin_key = key.relin_key(dnum);
+        assert_eq!(relin_key.dnum(), dnum);
+
+        let cipherz = (&cipherx * &ciphery).relin(&relin_key);
+        let z = decode(decrypt(key.private(), &cipherz), &encoder);
+
+        for (&x, y) in expected_z.iter().zip(z) {
+            assert_relative_eq!(x, y.re, epsilon = 1e-4)
+        }
+    }
+}
+
+#[test]
+fn mul_relin_rns_modulus() {
+    let poly_degree = 16;
+    let crt = Crt::new(20, 30, poly_degree as u64);
+    let ciph_modulus = crt.modulus.clone();
+    // Rescale by the last prime, so the scale stays close to 2^30
+    let scaling_factor = *crt.primes().last().unwrap();
+    let division = scaling_factor.to_bigint().unwrap();
+
+    let key = Rwle::keygen(&ciph_modulus, poly_degree, poly_degree).add_crt(&crt);
+    let encoder = encoder::CKKSEncoder::new(poly_degree * 2);
+
+    let x = [0.05, 0.1, 1.0, 0.005, -0.5, 0.25, 0.3, -1.0];
+    let y = [0.1, 0.02, 0.5, 0.3, 0.7, -0.2, 1.0, 0.4];
+    let plainx = encode(&x, scaling_factor as usize, &encoder);
+    let plainy = encode(&y, scaling_factor as usize, &encoder);
+    let cipherx = encrypt(key.public(), &ciph_modulus, &plainx);
+    let ciphery = encrypt(key.public(), &ciph_modulus, &plainy);
+
+    for dnum in [1, 3, 20] {
+        let relin_key = key.relin_key(dnum);
+        // The digits are groups of the primes of the ciphertext modulus
+        assert_eq!(relin_key.primes(), crt.primes());
+        assert!(relin_key
+            .special_primes()
+            .iter()
+            .all(|p| !crt.primes().contains(p)));
+
+        let cipherz = (&cipherx * &ciphery).relin(&relin_key).rescale(&division);
+        let z = decode(decrypt(key.private(), &cipherz), &encoder);
+        for ((a, b), z) in x.iter().zip(&y).zip(z) {
+            assert_relative_eq!(a * b, z.re, epsilon = 1e-4)
+        }
+
+        // One prime below the modulus of the key
+        let squared = (&cipherz * &cipherz).relin(&relin_key);
+        let z = decode(decrypt(key.private(), &squared), &encoder);
+        for ((a, b), z) in x.iter().zip(&y).zip(z) {
+            assert_relative_eq!(a * b * a * b, z.re, epsilon = 1e-4)
+        }
+    }
+}
+
+#[test]
+fn mul_coeffs() {
+    let poly_degree = 8;
+    let ciph_modulus = 1.to_bigint().unwrap() << 600;
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&ciph_modulus, poly_degree, poly_degree);
+    let relin_key = key.relin_key(3);
+    let encoder = encoder::CKKSEncoder::new(poly_degree * 2);
+
+    let x = [0.5, 0.25, 0., 0., 0., 0., 0., 0.75];
+    let y = [0.1, 0.2, 0.3];
+    let plainx = encode_coeffs(&x, scaling_factor, &encoder).unwrap();
+    let plainy = encode_coeffs(&y, scaling_factor, &encoder).unwrap();
+
+    assert_eq!(
+        encode_coeffs(&[0.; 9], scaling_factor, &encoder).unwrap_err(),
+        Error::TooManyCoefficients {
+            len: 9,
+            poly_degree: 8
+        }
+    );
+
+    let cipherx = encrypt(key.public(), &ciph_modulus, &plainx);
+    let ciphery = encrypt(key.public(), &ciph_modulus, &plainy);
+
+    let cipherz = (&cipherx * &ciphery).relin(&relin_key);
+    let z = decode_coeffs(decrypt(key.private(), &cipherz));
+
+    // Negacyclic convolution: X^8 = -1
+    let expected_z = [0.05 - 0.15, 0.125 - 0.225, 0.2, 0.075, 0., 0., 0., 0.075];
+    assert_eq!(z.len(), poly_degree);
+    for (&x, y) in expected_z.iter().zip(z) {
+        assert_relative_eq!(x, y, epsilon = 1e-4)
+    }
+}
+
+#[test]
+fn rotate() {
+    let poly_degree = 8 * 2;
+    let ciph_modulus = 1.to_bigint().unwrap() << 600;
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&ciph_modulus, poly_degree as usize, poly_degree as usize);
+    let encoder = encoder::CKKSEncoder::new(poly_degree as usize * 2);
+
+    let x = [0.05, 0.1, 1.0, 0.005, 0.7, 0.3, 0.25, 0.6];
+    let plainx = encode(&x, scaling_factor, &encoder);
+    let cipherx = encrypt(key.public(), &ciph_modulus, &plainx);
+
+    for rotation in [1, 3, 5] {
+        let rot_key = key.rotation_key(rotation, 4);
+        let rotated = cipherx.rotate(rotation, &rot_key);
+        let z = decode(decrypt(key.private(), &rotated), &encoder);
+
+        for (i, y) in z.iter().enumerate() {
+            assert_relative_eq!(x[(i + rotation) % x.len()], y.re, epsilon = 1e-4)
+        }
+    }
+}
+
+#[test]
+fn rotate_sparse() {
+    use num_complex::Complex64;
+
+    let poly_degree = 16 * 2;
+    let ciph_modulus = 1.to_bigint().unwrap() << 600;
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&ciph_modulus, poly_degree, poly_degree);
+    let relin_key = key.relin_key(4);
+    let rot_key = key.rotation_key(1, 4);
+    let encoder = encoder::CKKSEncoder::new(poly_degree * 2);
+
+    let x = [0.05, 0.1, 1.0, 0.005];
+    let message: Vec<Complex64> = x.iter().map(|&v| Complex64::new(v, 0.)).collect();
+    let plainx = encode_sparse(&message, 4, scaling_factor, &encoder).unwrap();
+    let cipherx = encrypt(key.public(), &ciph_modulus, &plainx);
+
+    // A rotation by 5 is a rotation by 1 when there are 4 slots
+    for rotation in [1, 5] {
+        let rotated = cipherx.rotate(rotation, &rot_key);
+        let z = decode(decrypt(key.private(), &rotated), &encoder);
+        assert_eq!(z.len(), 4);
+
+        for (i, y) in z.iter().enumerate() {
+            assert_relative_eq!(x[(i + 1) % 4], y.re, epsilon = 1e-4)
+        }
+    }
+
+    let squared = (&cipherx * &cipherx).relin(&relin_key);
+    let z = decode(decrypt(key.private(), &squared), &encoder);
+    for (&x, y) in x.iter().zip(z) {
+        assert_relative_eq!(x * x, y.re, epsilon = 1e-4)
+    }
+}
+
+#[test]
+fn conjugate() {
+    let poly_degree = 4 * 2;
+    let ciph_modulus = 1.to_bigint().unwrap() << 600;
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&ciph_modulus, poly_degree as usize, poly_degree as usize);
+    let conj_key = key.conjugation_key(4);
+    let encoder = encoder::CKKSEncoder::new(poly_degree as usize * 2);
+
+    let x = [0.05, 0.1, 1.0, 0.005];
+    let plainx = encode(&x, scaling_factor, &encoder);
+    let cipherx = encrypt(key.public(), &ciph_modulus, &plainx);
+
+    let conjugated = cipherx.conjugate(&conj_key);
+    let z = decode(decrypt(key.private(), &conjugated), &encoder);
+
+    for (&x, y) in x.iter().zip(z) {
+        assert_relative_eq!(x, y.re, epsilon = 1e-4);
+        assert_relative_eq!(0., y.im, epsilon = 1e-4);
+    }
+}
+
 #[test]
 fn reverse_bits() {
     use algebra::utils::reverse_bits;
@@ -200,7 +592,7 @@ fn mnist() {
     };
     let plain = encode(&padded_message, scaling_factor, &encoder);
     let cipher = encrypt(key.public(), &ciph_modulus, &plain);
-    let out = decrypt(key.private(), cipher);
+    let out = decrypt(key.private(), &cipher);
     let img = decode(out, &encoder);
 
     let mut img: Vec<u8> = img.iter().map(|x| (x.re * 255.) as u8).collect();
@@ -277,7 +669,7 @@ fn mnist_crt() {
     };
     let plain = encode(&padded_message, scaling_factor, &encoder);
     let cipher = encrypt(key.public(), &ciph_modulus, &plain);
-    let out = decrypt(key.private(), cipher);
+    let out = decrypt(key.private(), &cipher);
     let img = decode(out, &encoder);
 
     let mut img: Vec<u8> = img.iter().map(|x| (x.re * 255.) as u8).collect();
@@ -288,3 +680,559 @@ fn mnist_crt() {
         assert!((four_img[i] as i32 - img[i] as i32).abs() <= 1);
     }
 }
+
+#[test]
+fn mul_relin_ntt_key() {
+    let poly_degree = 8;
+    let log_modulus = 600;
+    let ciph_modulus = 1.to_bigint().unwrap() << log_modulus;
+    let scaling_factor = 1_usize << 30;
+
+    let prime_size = 30;
+    let num_primes = (2 + 3 + 4 * log_modulus as u64).div_ceil(prime_size);
+    let crt = Crt::new(num_primes, prime_size, poly_degree);
+
+    let key = Rwle::keygen(&ciph_modulus, poly_degree as usize, poly_degree as usize).add_crt(&crt);
+    let relin_key = key.relin_key(3);
+    let encoder = encoder::CKKSEncoder::new(poly_degree as usize * 2);
+
+    let x = [0.05, 0.1, 1.0, 0.005];
+    let y = [0.1, 0.02, 0.5, 0.3];
+    let plainx = encode(&x, scaling_factor, &encoder);
+    let plainy = encode(&y, scaling_factor, &encoder);
+
+    let cipherx = encrypt(key.public(), &ciph_modulus, &plainx);
+    let ciphery = encrypt(key.public(), &ciph_modulus, &plainy);
+
+    let cipherz = (&cipherx * &ciphery).relin(&relin_key);
+    let z = decode(decrypt(key.private(), &cipherz), &encoder);
+
+    for ((a, b), z) in x.iter().zip(&y).zip(z) {
+        assert_relative_eq!(a * b, z.re, epsilon = 1e-4)
+    }
+}
+
+#[test]
+fn fresh_noise() {
+    let poly_degree = 16;
+    let ciph_modulus = 1.to_bigint().unwrap() << 600;
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&ciph_modulus, poly_degree, poly_degree);
+    let encoder = encoder::CKKSEncoder::new(poly_degree * 2);
+
+    let x = [0.5, -0.25, 1.0, 0.125];
+    let plain = encode(&x, scaling_factor, &encoder);
+
+    // The embedding of the plaintext gives back the scaled message, up to the rounding of every
+    // coefficient
+    let embedding = plain.poly.canonical_embedding(&encoder);
+    for (&x, y) in x.iter().zip(&embedding) {
+        assert_relative_eq!(
+            x * scaling_factor as f64,
+            y.re,
+            epsilon = poly_degree as f64
+        );
+    }
+
+    let cipher = encrypt(key.public(), &ciph_modulus, &plain);
+    let decrypted = decrypt(key.private(), &cipher);
+    let noise = (&decrypted.poly - &plain.poly) % &ciph_modulus;
+
+    // e * u + e1 + e2 * s, with Gaussian samples of standard deviation 2 that are below 6 sigma
+    // with overwhelming probability, and binary u and s
+    let bound = 12 * (2 * poly_degree + 1);
+    assert!(noise.inf_norm() <= bound.to_bigint().unwrap());
+    assert!(noise.l2_norm() <= (poly_degree as f64).sqrt() * bound as f64);
+    assert!(noise.canonical_inf_norm(&encoder) <= (poly_degree * bound) as f64);
+    assert!(
+        noise.canonical_inf_norm(&encoder) <= (poly_degree as f64).sqrt() * noise.l2_norm() + 1e-6
+    );
+}
+
+#[test]
+fn decryptor() {
+    let n = 8;
+    let q = 1.to_bigint().unwrap() << 600;
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&q, n, n);
+    let encoder = encoder::CKKSEncoder::new(n * 2);
+    let decryptor = key.decryptor(2);
+    assert_eq!(decryptor.max_power(), 2);
+
+    let x = [0.05, 0.1, 1.0, 0.005];
+    let plain = encode(&x, scaling_factor, &encoder);
+    let cipher = encrypt(key.public(), &q, &plain);
+
+    // Decrypting leaves the ciphertext as it is
+    let first = decrypt(key.private(), &cipher);
+    let second = decryptor.decrypt(&cipher);
+    assert_eq!(first.poly, second.poly);
+
+    let (noise, bits) = decryptor.decrypt_with_noise(&cipher, &plain);
+    assert_eq!(noise, (&second.poly - &plain.poly) % &q);
+    assert_eq!(bits, noise.inf_norm().bits());
+    // Fresh noise is e * u + e1 + e2 * s, far below the scaling factor
+    assert!(bits < 20);
+
+    // Ciphertexts of dimension 3 use the cached s^2
+    let squared = &cipher * &cipher;
+    let z = decode(decryptor.decrypt(&squared), &encoder);
+    for (a, z) in x.iter().zip(z) {
+        assert_relative_eq!(a * a, z.re, epsilon = 1e-4)
+    }
+}
+
+#[test]
+fn evaluator() {
+    let n = 16;
+    let q = 1.to_bigint().unwrap() << 600;
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&q, n, n);
+    let eval = Evaluator::new(&key, scaling_factor, 4, &[1, 3]);
+    assert_eq!(eval.rotations(), vec![1, 3]);
+    let encoder = eval.encoder();
+
+    let x = [0.05, 0.1, 1.0, 0.005, 0.7, 0.3, 0.25, 0.6];
+    let y = [0.1, 0.02, 0.5, 0.3, -0.4, 0.8, 0.9, -0.2];
+    let plainx = encode(&x, scaling_factor, encoder);
+    let plainy = encode(&y, scaling_factor, encoder);
+    let cipherx = encrypt(key.public(), &q, &plainx);
+    let ciphery = encrypt(key.public(), &q, &plainy);
+
+    let check = |ct: &CipherText<_, 2>, expected: &dyn Fn(usize) -> f64| {
+        assert_eq!(ct.scaling_factor(), &(scaling_factor as u64).into());
+        let z = decode(decrypt(key.private(), ct), encoder);
+        for (i, z) in z.iter().enumerate() {
+            assert_relative_eq!(expected(i), z.re, epsilon = 1e-4)
+        }
+    };
+
+    // Every product uses up one level and comes back at the scaling factor
+    let xy = eval.mul(&cipherx, &ciphery).unwrap();
+    assert_eq!(xy.modulus(), &(&q >> 30));
+    check(&xy, &|i| x[i] * y[i]);
+
+    // (x * y)^2 * 3x - y + 0.5, with operands at different levels
+    let xy2 = eval.square(&xy).unwrap();
+    let x3 = eval.mul_plain(&cipherx, &[3.; 8]).unwrap();
+    let z = eval.mul(&xy2, &x3).unwrap();
+    assert_eq!(z.modulus(), &(&q >> 90));
+    let z = eval.sub(&z, &ciphery).unwrap();
+    let z = eval.add_const(&z, 0.5);
+    check(&z, &|i| (x[i] * y[i]).powi(2) * 3. * x[i] - y[i] + 0.5);
+
+    let z = eval.level_down(&cipherx, xy.modulus());
+    let z = eval.add(&eval.negate(&z), &xy).unwrap();
+    assert_eq!(z.modulus(), xy.modulus());
+    check(&z, &|i| x[i] * y[i] - x[i]);
+
+    let z = eval.rotate(&xy, 11).unwrap();
+    check(&z, &|i| x[(i + 3) % 8] * y[(i + 3) % 8]);
+    let z = eval.conjugate(&z);
+    check(&z, &|i| x[(i + 3) % 8] * y[(i + 3) % 8]);
+}
+
+#[test]
+fn evaluator_errors() {
+    let n = 8;
+    let q = 1.to_bigint().unwrap() << 90;
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&q, n, n);
+    let eval = Evaluator::new(&key, scaling_factor, 2, &[]);
+
+    let x = [0.5, 0.25];
+    let plainx = encode(&x, scaling_factor, eval.encoder());
+    let plainy = encode(&x, 3 << 29, eval.encoder());
+    let cipherx = encrypt(key.public(), &q, &plainx);
+    let ciphery = encrypt(key.public(), &q, &plainy);
+
+    assert_eq!(
+        eval.rotate(&cipherx, 1).unwrap_err(),
+        Error::MissingRotationKey { rotation: 1 }
+    );
+    assert_eq!(
+        eval.add(&cipherx, &ciphery).unwrap_err(),
+        Error::ScaleMismatch {
+            left: (1_u64 << 30).into(),
+            right: (3_u64 << 29).into()
+        }
+    );
+
+    // A product at 9 * 2^24 cannot be rescaled by Δ = 2^30
+    let plainw = encode(&x, 3 << 12, eval.encoder());
+    let cipherw = encrypt(key.public(), &q, &plainw);
+    assert_eq!(
+        eval.square(&cipherw).unwrap_err(),
+        Error::ScaleMismatch {
+            left: (9_u64 << 24).into(),
+            right: (1_u64 << 30).into()
+        }
+    );
+
+    // 2^90 leaves room for two products
+    let z = eval.square(&cipherx).unwrap();
+    let z = eval.square(&z).unwrap();
+    assert_eq!(
+        eval.square(&z).unwrap_err(),
+        Error::OutOfLevels {
+            modulus: 1.to_bigint().unwrap() << 30
+        }
+    );
+}
+
+#[test]
+fn evaluator_crt_modulus() {
+    let n = 16;
+    let primes = algebra::prime::ntt_primes(&[30; 10], n as u64);
+    let crt = Crt::from_primes(&primes, n as u64);
+    let q = crt.modulus.clone();
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&q, n, n).add_crt(&crt);
+    let eval = Evaluator::new(&key, scaling_factor, 2, &[1]);
+
+    let x = [0.5, -1.0, 2.0, 0.25, -3.0, 1.5, 0.75, -0.5];
+    let plainx = encode(&x, scaling_factor, eval.encoder());
+    let cipherx = encrypt(key.public(), &q, &plainx);
+
+    // Nothing that rescales works over the primes
+    let mismatch = Error::ModulusNotMultipleOfScale {
+        modulus: q.clone(),
+        scaling_factor: scaling_factor.to_bigint().unwrap(),
+    };
+    assert_eq!(eval.square(&cipherx).unwrap_err(), mismatch);
+    assert_eq!(eval.mul_plain(&cipherx, &x).unwrap_err(), mismatch);
+    assert_eq!(eval.rescale(&cipherx).unwrap_err(), mismatch);
+
+    // The rest does
+    let sum = eval
+        .add(&cipherx, &eval.rotate(&cipherx, 1).unwrap())
+        .unwrap();
+    let sum = eval.add_const(&eval.mul_integer(&sum, 2), 1.);
+    let z = decode(decrypt(key.private(), &sum), eval.encoder());
+    for i in 0..8 {
+        assert_relative_eq!(2. * (x[i] + x[(i + 1) % 8]) + 1., z[i].re, epsilon = 1e-4);
+    }
+}
+
+#[test]
+fn circuit() {
+    use std::collections::HashMap;
+
+    let n = 16;
+    let q = 1.to_bigint().unwrap() << 600;
+    let scaling_factor = 1_usize << 30;
+
+    let x = [0.5, -1.0, 2.0, 0.25, -3.0, 1.5, 0.75, -0.5];
+    let w = [1.0, 0.5, -0.5, 2.0, 0.25, -1.0, 1.5, 0.5];
+    let b = [0.1, -0.2, 0.3, 0.0, 0.5, -0.4, 0.2, 0.1];
+
+    // A neuron, and the sum of neighbouring products
+    let xw = Expr::input("x") * Expr::input("w");
+    let y = (&xw + Expr::plain(&b)).sigmoid();
+    let s = &xw + xw.rotate(1) - 1.;
+    let plan = Plan::compile(&[("y", y), ("s", s)], n / 2);
+
+    assert_eq!(plan.inputs(), ["x", "w"]);
+    assert_eq!(plan.rotations(), vec![1]);
+    // x * w, x^2, x^3, and the product with the coefficient of x^3
+    assert_eq!(plan.depth(), 4);
+    // The shared x * w is computed once for both outputs
+    let products = plan
+        .instructions()
+        .iter()
+        .filter(|ins| matches!(ins, circuit::Instruction::Multiply(..)))
+        .count();
+    assert_eq!(products, 3);
+
+    let key = Rwle::keygen(&q, n, n);
+    let eval = plan.evaluator(&key, scaling_factor, 4);
+    assert_eq!(eval.rotations(), vec![1]);
+
+    let plainx = encode(&x, scaling_factor, eval.encoder());
+    let plainw = encode(&w, scaling_factor, eval.encoder());
+    let mut inputs = HashMap::new();
+    inputs.insert("x", encrypt(key.public(), &q, &plainx));
+    inputs.insert("w", encrypt(key.public(), &q, &plainw));
+
+    let outputs = plan.execute(&eval, &inputs).unwrap();
+
+    let sigmoid = polyr::Approximation::remez(|t| 1. / (1. + (-t).exp()), -8., 8., 3);
+    let y = decode(decrypt(key.private(), &outputs["y"]), eval.encoder());
+    for i in 0..8 {
+        assert_relative_eq!(sigmoid.eval(x[i] * w[i] + b[i]), y[i].re, epsilon = 1e-3);
+    }
+    let s = decode(decrypt(key.private(), &outputs["s"]), eval.encoder());
+    for i in 0..8 {
+        let expected = x[i] * w[i] + x[(i + 1) % 8] * w[(i + 1) % 8] - 1.;
+        assert_relative_eq!(expected, s[i].re, epsilon = 1e-4);
+    }
+
+    inputs.remove("w");
+    assert_eq!(
+        plan.execute(&eval, &inputs).unwrap_err(),
+        Error::MissingInput {
+            name: "w".to_string()
+        }
+    );
+}
+
+#[test]
+fn circuit_integer_constants() {
+    use std::collections::HashMap;
+
+    let n = 16;
+    let q = 1.to_bigint().unwrap() << 300;
+    let scaling_factor = 1_usize << 30;
+
+    let x = [0.5, -1.0, 2.0, 0.25, -3.0, 1.5, 0.75, -0.5];
+
+    // Integer constants keep the level, 0.5 does not
+    let y = Expr::input("x") * 3. - 2. * Expr::input("x").square() * -4.;
+    let plan = Plan::compile(&[("y", y)], n / 2);
+    assert_eq!(plan.depth(), 1);
+    assert!(plan
+        .instructions()
+        .contains(&circuit::Instruction::MulInteger(0, 3)));
+
+    let half = Plan::compile(&[("y", Expr::input("x") * 0.5)], n / 2);
+    assert_eq!(half.depth(), 1);
+    let one = Plan::compile(&[("y", Expr::input("x") * 1.)], n / 2);
+    assert_eq!(one.depth(), 0);
+    assert_eq!(one.instructions().len(), 1);
+
+    let key = Rwle::keygen(&q, n, n);
+    let eval = plan.evaluator(&key, scaling_factor, 4);
+    let plainx = encode(&x, scaling_factor, eval.encoder());
+    let mut inputs = HashMap::new();
+    inputs.insert("x", encrypt(key.public(), &q, &plainx));
+
+    let outputs = plan.execute(&eval, &inputs).unwrap();
+    assert_eq!(outputs["y"].modulus(), &(&q >> 30));
+    let y = decode(decrypt(key.private(), &outputs["y"]), eval.encoder());
+    for i in 0..8 {
+        assert_relative_eq!(3. * x[i] + 8. * x[i] * x[i], y[i].re, epsilon = 1e-4);
+    }
+}
+
+#[test]
+fn mul_integer() {
+    let n = 8;
+    let q = 1.to_bigint().unwrap() << 300;
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&q, n, n);
+    let encoder = encoder::CKKSEncoder::new(n * 2);
+
+    let x = [0.05, -0.1, 1.0, 0.5];
+    let plainx = encode(&x, scaling_factor, &encoder);
+    let cipherx = encrypt(key.public(), &q, &plainx);
+
+    // The values are kept at 3 times the scale
+    let tripled = cipherx.mul_integer(&3.to_bigint().unwrap()).unwrap();
+    assert_eq!(tripled.scaling_factor(), &(3_u64 << 30).into());
+    let z = decode(decrypt(key.private(), &tripled), &encoder);
+    for (a, z) in x.iter().zip(z) {
+        assert_relative_eq!(*a, z.re, epsilon = 1e-6)
+    }
+
+    // A negative k negates the values, at a positive scale
+    let negated = cipherx.mul_integer(&(-3).to_bigint().unwrap()).unwrap();
+    assert_eq!(negated.scaling_factor(), &(3_u64 << 30).into());
+    let z = decode(decrypt(key.private(), &negated), &encoder);
+    for (a, z) in x.iter().zip(z) {
+        assert_relative_eq!(-a, z.re, epsilon = 1e-6)
+    }
+
+    assert_eq!(
+        cipherx.mul_integer(&0.to_bigint().unwrap()).unwrap_err(),
+        Error::ZeroScale
+    );
+}
+
+#[test]
+fn add_aligned() {
+    let n = 8;
+    let q = 1.to_bigint().unwrap() << 600;
+    let scaling_factor = 1_usize << 30;
+
+    let key = Rwle::keygen(&q, n, n);
+    let relin_key = key.relin_key(4);
+    let encoder = encoder::CKKSEncoder::new(n * 2);
+
+    let x = [0.05, 0.1, 1.0, 0.005];
+    let y = [0.1, 0.02, 0.5, 0.3];
+    let plainx = encode(&x, scaling_factor, &encoder);
+    let plainy = encode(&y, scaling_factor, &encoder);
+    let cipherx = encrypt(key.public(), &q, &plainx);
+    let ciphery = encrypt(key.public(), &q, &plainy);
+
+    // x * y at Δ^2, plus y at Δ
+    let xy = (&cipherx * &ciphery).relin(&relin_key);
+    let delta_squared = num_bigint::BigUint::from(1_u64 << 60);
+
+    let z = xy.add_aligned(&ciphery, ScaleAlignment::Multiply).unwrap();
+    assert_eq!(z.scaling_factor(), &delta_squared);
+    assert_eq!(z.modulus(), &q);
+    let z = decode(decrypt(key.private(), &z), &encoder);
+    for ((a, b), z) in x.iter().zip(&y).zip(z) {
+        assert_relative_eq!(a * b + b, z.re, epsilon = 1e-4)
+    }
+
+    let z = ciphery.sub_aligned(&xy, ScaleAlignment::Rescale).unwrap();
+    assert_eq!(z.scaling_factor(), &(scaling_factor as u64).into());
+    assert_eq!(z.modulus(), &(&q >> 30));
+    let z = decode(decrypt(key.private(), &z), &encoder);
+    for ((a, b), z) in x.iter().zip(&y).zip(z) {
+        assert_relative_eq!(b - a * b, z.re, epsilon = 1e-4)
+    }
+
+    // A ratio of 3 can be multiplied in, but does not divide the modulus
+    let plainw = encode(&y, 3 << 30, &encoder);
+    let cipherw = encrypt(key.public(), &q, &plainw);
+    let z = cipherx
+        .add_aligned(&cipherw, ScaleAlignment::Multiply)
+        .unwrap();
+    let z = decode(decrypt(key.private(), &z), &encoder);
+    for ((a, b), z) in x.iter().zip(&y).zip(z) {
+        assert_relative_eq!(a + b, z.re, epsilon = 1e-4)
+    }
+    let mismatch = Error::ScaleMismatch {
+        left: (1_u64 << 30).into(),
+        right: (3_u64 << 30).into(),
+    };
+    assert_eq!(
+        cipherx
+            .add_aligned(&cipherw, ScaleAlignment::Rescale)
+            .unwrap_err(),
+        mismatch
+    );
+
+    // Neither scale is a multiple of the other
+    let plainv = encode(&y, 3 << 29, &encoder);
+    let cipherv = encrypt(key.public(), &q, &plainv);
+    assert!(cipherx
+        .add_aligned(&cipherv, ScaleAlignment::Multiply)
+        .is_err());
+}
+
+#[test]
+fn bfv_batching() {
+    use algebra::prime::ntt_primes;
+    use bfv::Bfv;
+
+    let n = 16;
+    let q = 1.to_bigint().unwrap() << 200;
+    let t = ntt_primes(&[20], n as u64)[0];
+
+    let key = Rwle::keygen(&q, n, n);
+    let relin_key = key.relin_key(4);
+    let bfv = Bfv::new(&q, t, n);
+    assert_eq!(bfv.num_slots(), n);
+
+    let a: Vec<u64> = (0..n as u64).map(|i| i * 12345 % t).collect();
+    let b: Vec<u64> = (0..n as u64).map(|i| (t - 1 - i * 777) % t).collect();
+    let plaina = bfv.encode(&a).unwrap();
+    let plainb = bfv.encode(&b).unwrap();
+
+    // Plaintexts are polynomials modulo t, whose products are slot by slot
+    let product = bfv.decode(&bfv::PlainText {
+        poly: &plaina.poly * &plainb.poly,
+    });
+    let expected: Vec<u64> = a.iter().zip(&b).map(|(x, y)| x * y % t).collect();
+    assert_eq!(product.unwrap(), expected);
+
+    let ciphera = bfv.encrypt(key.public(), &plaina);
+    let cipherb = bfv.encrypt(key.public(), &plainb);
+    let sk = key.private();
+    let dec = |ct: &bfv::CipherText| bfv.decode(&bfv.decrypt(sk, ct)).unwrap();
+
+    assert_eq!(dec(&ciphera), a);
+    let sum: Vec<u64> = a.iter().zip(&b).map(|(x, y)| (x + y) % t).collect();
+    assert_eq!(dec(&(&ciphera + &cipherb)), sum);
+    let diff: Vec<u64> = a.iter().zip(&b).map(|(x, y)| (x + t - y) % t).collect();
+    assert_eq!(dec(&(&ciphera - &cipherb)), diff);
+    assert_eq!(dec(&-&(&cipherb - &ciphera)), diff);
+
+    let fresh = bfv.noise_budget(sk, &ciphera);
+    let product = bfv.mul(&ciphera, &cipherb, &relin_key);
+    assert_eq!(dec(&product), expected);
+    assert!(bfv.noise_budget(sk, &product) < fresh);
+
+    // a * b * b + a, with plaintext operations
+    let z = bfv.add_plain(&bfv.mul_plain(&product, &plainb), &plaina);
+    let expected: Vec<u64> = a
+        .iter()
+        .zip(&b)
+        .map(|(x, y)| (x * y % t * y + x) % t)
+        .collect();
+    assert_eq!(dec(&z), expected);
+}
+
+#[test]
+fn bfv_coefficients() {
+    let n = 8;
+    let q = 1.to_bigint().unwrap() << 120;
+    let t = 256;
+
+    let key = Rwle::keygen(&q, n, n);
+    let relin_key = key.relin_key(2);
+    let bfv = bfv::Bfv::new(&q, t, n);
+
+    // 256 is not a prime, so there are no slots
+    assert_eq!(bfv.num_slots(), 0);
+    assert_eq!(
+        bfv.encode(&[1, 2]).unwrap_err(),
+        Error::NoBatching {
+            plain_modulus: 256,
+            poly_degree: 8
+        }
+    );
+    assert!(bfv.encode_coeffs(&[0; 9]).is_err());
+
+    // (1 + 255 X^7)^2 = 1 + 254 X^7 + X^14 = 1 + 254 X^7 - X^6 (mod X^8 + 1, 256)
+    let plain = bfv.encode_coeffs(&[1, 0, 0, 0, 0, 0, 0, 255]).unwrap();
+    let cipher = bfv.encrypt(key.public(), &plain);
+    let squared = bfv.mul(&cipher, &cipher, &relin_key);
+    assert_eq!(
+        bfv.decode_coeffs(&bfv.decrypt(key.private(), &squared)),
+        vec![1, 0, 0, 0, 0, 0, 255, 254]
+    );
+}
+
+#[test]
+fn bfv_crt() {
+    use algebra::prime::ntt_primes;
+
+    let n = 16;
+    let q = 1.to_bigint().unwrap() << 200;
+    let t = ntt_primes(&[20], n as u64)[0];
+
+    // 330 bits hold the key switching products, but not the N q^2 of the tensor product
+    let crt = Crt::new(11, 30, n as u64);
+    let key = Rwle::keygen(&q, n, n).add_crt(&crt);
+    let relin_key = key.relin_key(4);
+    let bfv = bfv::Bfv::new(&q, t, n);
+
+    let a: Vec<u64> = (0..n as u64).map(|i| (i + 1) * 54321 % t).collect();
+    let plain = bfv.encode(&a).unwrap();
+    let cipher = bfv.encrypt(key.public(), &plain);
+
+    let squared = bfv.mul(&cipher, &cipher, &relin_key);
+    let expected: Vec<u64> = a.iter().map(|x| x * x % t).collect();
+    assert_eq!(
+        bfv.decode(&bfv.decrypt(key.private(), &squared)).unwrap(),
+        expected
+    );
+}
+
+#[test]
+#[should_panic]
+fn bfv_plain_modulus_too_small() {
+    let q = 1.to_bigint().unwrap() << 100;
+    bfv::Bfv::new(&q, 1, 8);
+}